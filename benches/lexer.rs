@@ -0,0 +1,41 @@
+//! Lexes generated `.ilu` sources of growing size and reports throughput.
+//! Run with `cargo bench --bench lexer`; time per byte should stay flat as
+//! the input doubles, which it doesn't for a quadratic lexer.
+
+use std::time::Instant;
+
+use ilumnia::compiler::lexer::Lexer;
+
+/// A few megabytes of valid source, mixing every kind of token and some
+/// non-ASCII text.
+fn generate(target_len: usize) -> String {
+    let mut source = String::with_capacity(target_len + 256);
+    let mut i = 0;
+
+    while source.len() < target_len {
+        source.push_str(&format!(
+            "func step_{i}(a, b) {{\n    let größe_{i} = a * {i} + b / 3;\n    if (größe_{i} > 100) {{\n        print \"überlauf → {i}\";\n    }}\n    return größe_{i} - 1;\n}}\nlet x_{i} = step_{i}({i}, 42);\n"
+        ));
+        i += 1;
+    }
+
+    source
+}
+
+fn main() {
+    for megabytes in [1, 2, 4, 8] {
+        let source = generate(megabytes * 1024 * 1024);
+
+        let started = Instant::now();
+        let tokens = Lexer::new(&source).lex().expect("generated source lexes");
+        let elapsed = started.elapsed();
+
+        println!(
+            "{:>2} MiB: {:>9} tokens in {:>8.2?} ({:.2} ns/byte)",
+            megabytes,
+            tokens.len(),
+            elapsed,
+            elapsed.as_nanos() as f64 / source.len() as f64
+        );
+    }
+}
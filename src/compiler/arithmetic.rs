@@ -0,0 +1,372 @@
+//! Numeric operators. Integer arithmetic is checked, so overflow is a runtime
+//! error rather than a panic or a silently wrapped result. With the `bigint`
+//! feature, integer results that don't fit in an `i64` become big integers.
+
+use std::cmp::Ordering;
+
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use super::evaluator::{RuntimeError, Value, ValueType};
+use super::span::Span;
+use super::token::{Token, TokenType};
+
+/// Applies an arithmetic operator to two numbers. `None` means the operator
+/// isn't arithmetic or an operand isn't a number.
+pub fn apply(
+    operator: &Token,
+    left: &Value,
+    right: &Value,
+    span: Span,
+) -> Option<Result<Value, RuntimeError>> {
+    if !matches!(
+        operator.token_type,
+        TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Asterisk
+            | TokenType::Slash
+            | TokenType::Percent
+            | TokenType::Power
+    ) {
+        return None;
+    }
+
+    if let Some((left, right)) = promote(left, right) {
+        return Some(float(operator, left, right, span));
+    }
+
+    match (&left.value_type, &right.value_type) {
+        (ValueType::Integer(left), ValueType::Integer(right)) => {
+            Some(integer(operator, *left, *right, span))
+        }
+        #[cfg(feature = "bigint")]
+        _ => Some(big(operator, to_big(left)?, to_big(right)?, span)),
+        #[cfg(not(feature = "bigint"))]
+        _ => None,
+    }
+}
+
+/// Applies unary `-` or `+` to a number. `None` means the operand isn't a number.
+pub fn apply_unary(
+    operator: &Token,
+    operand: &Value,
+    // Negation can only overflow without big integers.
+    #[cfg_attr(feature = "bigint", allow(unused_variables))] span: Span,
+) -> Option<Result<Value, RuntimeError>> {
+    if operator.token_type == TokenType::Plus {
+        return as_float(operand).map(|_| Ok(operand.clone()));
+    }
+
+    let value = match &operand.value_type {
+        ValueType::Integer(value) => match value.checked_neg() {
+            Some(value) => Value::new(ValueType::Integer(value)),
+            #[cfg(feature = "bigint")]
+            None => from_big(-BigInt::from(*value)),
+            #[cfg(not(feature = "bigint"))]
+            None => {
+                return Some(Err(RuntimeError::IntegerOverflow {
+                    operator: operator.value.clone(),
+                    span,
+                }))
+            }
+        },
+        ValueType::Float(value) => Value::new(ValueType::Float(-value)),
+        #[cfg(feature = "bigint")]
+        ValueType::BigInt(value) => from_big(-value),
+        _ => return None,
+    };
+
+    Some(Ok(value))
+}
+
+/// Orders two numbers, or returns `None` if either isn't a number. The inner
+/// `None` is for NaN, which is unordered.
+pub fn compare(left: &Value, right: &Value) -> Option<Option<Ordering>> {
+    if let Some((left, right)) = promote(left, right) {
+        return Some(left.partial_cmp(&right));
+    }
+
+    match (&left.value_type, &right.value_type) {
+        (ValueType::Integer(left), ValueType::Integer(right)) => Some(Some(left.cmp(right))),
+        #[cfg(feature = "bigint")]
+        _ => Some(Some(to_big(left)?.cmp(&to_big(right)?))),
+        #[cfg(not(feature = "bigint"))]
+        _ => None,
+    }
+}
+
+/// Both operands as floats, if they are numbers and at least one is a float.
+/// This is the promotion rule for mixed arithmetic: `1 + 0.5` is `1.5`, while
+/// two integers stay integers.
+fn promote(left: &Value, right: &Value) -> Option<(f64, f64)> {
+    if !matches!(left.value_type, ValueType::Float(_))
+        && !matches!(right.value_type, ValueType::Float(_))
+    {
+        return None;
+    }
+
+    Some((as_float(left)?, as_float(right)?))
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match &value.value_type {
+        ValueType::Integer(value) => Some(*value as f64),
+        ValueType::Float(value) => Some(*value),
+        #[cfg(feature = "bigint")]
+        ValueType::BigInt(value) => value.to_f64(),
+        _ => None,
+    }
+}
+
+fn integer(operator: &Token, left: i64, right: i64, span: Span) -> Result<Value, RuntimeError> {
+    let result = match operator.token_type {
+        TokenType::Plus => left.checked_add(right),
+        TokenType::Minus => left.checked_sub(right),
+        TokenType::Asterisk => left.checked_mul(right),
+        TokenType::Slash | TokenType::Percent if right == 0 => {
+            return Err(RuntimeError::DivisionByZero { span })
+        }
+        TokenType::Slash => left.checked_div(right),
+        // Only `i64::MIN % -1` overflows, and its remainder is 0 anyway.
+        TokenType::Percent => Some(left.wrapping_rem(right)),
+        TokenType::Power if right < 0 => {
+            return Err(RuntimeError::NegativeExponent {
+                exponent: right,
+                span,
+            })
+        }
+        _ => match u32::try_from(right) {
+            Ok(exponent) => left.checked_pow(exponent),
+            Err(_) => small_power(left, right % 2 == 0),
+        },
+    };
+
+    match result {
+        Some(value) => Ok(Value::new(ValueType::Integer(value))),
+        #[cfg(feature = "bigint")]
+        None => big(operator, BigInt::from(left), BigInt::from(right), span),
+        #[cfg(not(feature = "bigint"))]
+        None => Err(RuntimeError::IntegerOverflow {
+            operator: operator.value.clone(),
+            span,
+        }),
+    }
+}
+
+/// `base ** exponent` for an exponent too large to compute the power of,
+/// which still has a result when the base is 0, 1 or -1.
+fn small_power(base: i64, even_exponent: bool) -> Option<i64> {
+    match base {
+        0 | 1 => Some(base),
+        -1 if even_exponent => Some(1),
+        -1 => Some(-1),
+        _ => None,
+    }
+}
+
+/// Float arithmetic. `%` keeps the sign of the dividend, like integer `%`.
+fn float(operator: &Token, left: f64, right: f64, span: Span) -> Result<Value, RuntimeError> {
+    let value = match operator.token_type {
+        TokenType::Plus => left + right,
+        TokenType::Minus => left - right,
+        TokenType::Asterisk => left * right,
+        TokenType::Slash | TokenType::Percent if right == 0.0 => {
+            return Err(RuntimeError::DivisionByZero { span })
+        }
+        TokenType::Slash => left / right,
+        TokenType::Percent => left % right,
+        _ => left.powf(right),
+    };
+
+    Ok(Value::new(ValueType::Float(value)))
+}
+
+#[cfg(feature = "bigint")]
+fn to_big(value: &Value) -> Option<BigInt> {
+    match &value.value_type {
+        ValueType::Integer(value) => Some(BigInt::from(*value)),
+        ValueType::BigInt(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// An integer value, kept as an `i64` whenever it fits.
+#[cfg(feature = "bigint")]
+pub fn from_big(value: BigInt) -> Value {
+    match value.to_i64() {
+        Some(value) => Value::new(ValueType::Integer(value)),
+        None => Value::new(ValueType::BigInt(value)),
+    }
+}
+
+#[cfg(feature = "bigint")]
+fn big(operator: &Token, left: BigInt, right: BigInt, span: Span) -> Result<Value, RuntimeError> {
+    let result = match operator.token_type {
+        TokenType::Plus => left + right,
+        TokenType::Minus => left - right,
+        TokenType::Asterisk => left * right,
+        TokenType::Slash | TokenType::Percent if right.is_zero() => {
+            return Err(RuntimeError::DivisionByZero { span })
+        }
+        TokenType::Slash => left / right,
+        TokenType::Percent => left % right,
+        TokenType::Power if right.is_negative() => {
+            return Err(RuntimeError::NegativeExponent {
+                exponent: right.to_i64().unwrap_or(i64::MIN),
+                span,
+            })
+        }
+        // Even a big integer can't hold `2 ** (2 ** 32)` in reasonable memory.
+        _ => match right.to_u32() {
+            Some(exponent) => left.pow(exponent),
+            None => match left
+                .to_i64()
+                .and_then(|base| small_power(base, (&right % 2u32).is_zero()))
+            {
+                Some(value) => BigInt::from(value),
+                None => {
+                    return Err(RuntimeError::IntegerOverflow {
+                        operator: operator.value.clone(),
+                        span,
+                    })
+                }
+            },
+        },
+    };
+
+    Ok(from_big(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operator(token_type: TokenType, text: &str) -> Token {
+        Token::new(token_type, text.to_string(), Span::default())
+    }
+
+    fn int(value: i64) -> Value {
+        Value::new(ValueType::Integer(value))
+    }
+
+    fn binary(
+        token_type: TokenType,
+        text: &str,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        apply(&operator(token_type, text), &left, &right, Span::default())
+            .expect("operands should be numbers")
+    }
+
+    #[test]
+    fn promotes_mixed_operands_to_float() {
+        assert_eq!(
+            binary(
+                TokenType::Plus,
+                "+",
+                int(1),
+                Value::new(ValueType::Float(0.5))
+            )
+            .unwrap(),
+            Value::new(ValueType::Float(1.5))
+        );
+        assert_eq!(
+            binary(TokenType::Slash, "/", int(7), int(2)).unwrap(),
+            int(3)
+        );
+    }
+
+    #[test]
+    fn reports_division_by_zero() {
+        for token_type in [TokenType::Slash, TokenType::Percent] {
+            assert!(matches!(
+                binary(token_type, "/", int(1), int(0)),
+                Err(RuntimeError::DivisionByZero { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_negative_integer_exponents() {
+        assert!(matches!(
+            binary(TokenType::Power, "**", int(2), int(-1)),
+            Err(RuntimeError::NegativeExponent { exponent: -1, .. })
+        ));
+    }
+
+    #[test]
+    fn negates_integers() {
+        let negate = operator(TokenType::Minus, "-");
+
+        assert_eq!(
+            apply_unary(&negate, &int(i64::MAX), Span::default())
+                .unwrap()
+                .unwrap(),
+            int(-i64::MAX)
+        );
+        assert_eq!(
+            binary(TokenType::Minus, "-", int(-i64::MAX), int(1)).unwrap(),
+            int(i64::MIN)
+        );
+    }
+
+    #[test]
+    fn raises_small_bases_to_huge_exponents() {
+        let exponent = i64::from(u32::MAX) + 1;
+        for (base, expected) in [(0, 0), (1, 1), (-1, 1)] {
+            assert_eq!(
+                binary(TokenType::Power, "**", int(base), int(exponent)).unwrap(),
+                int(expected)
+            );
+        }
+        assert_eq!(
+            binary(TokenType::Power, "**", int(-1), int(exponent + 1)).unwrap(),
+            int(-1)
+        );
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn reports_overflow() {
+        let overflows = [
+            binary(TokenType::Plus, "+", int(i64::MAX), int(1)),
+            binary(TokenType::Minus, "-", int(i64::MIN), int(1)),
+            binary(TokenType::Asterisk, "*", int(i64::MAX), int(2)),
+            binary(TokenType::Slash, "/", int(i64::MIN), int(-1)),
+            binary(TokenType::Power, "**", int(2), int(63)),
+            apply_unary(
+                &operator(TokenType::Minus, "-"),
+                &int(i64::MIN),
+                Span::default(),
+            )
+            .unwrap(),
+        ];
+
+        for result in overflows {
+            assert!(
+                matches!(result, Err(RuntimeError::IntegerOverflow { .. })),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn overflows_into_big_integers() {
+        let sum = binary(TokenType::Plus, "+", int(i64::MAX), int(1)).unwrap();
+        assert_eq!(
+            sum,
+            Value::new(ValueType::BigInt(BigInt::from(i64::MAX) + 1))
+        );
+
+        // Results that fit again go back to plain integers.
+        assert_eq!(
+            binary(TokenType::Minus, "-", sum, int(1)).unwrap(),
+            int(i64::MAX)
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use super::span::Span;
 use super::token::Token;
 
 #[derive(Debug)]
@@ -8,18 +9,51 @@ pub struct Program {
 #[derive(Debug, Clone)]
 pub struct Statement {
     pub statement_type: StatementType,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub enum StatementType {
     Declaration(Declaration),
-    FunctionCall(String, Box<Vec<Expression>>),
+    /// A call used as a statement; always an `ExpressionType::Call`.
+    FunctionCall(Box<Expression>),
     Assignment(Assignment),
-    IfBlock(Box<Expression>, Box<Vec<Statement>>),
-    IfElseBlock(Box<Expression>, Box<Vec<Statement>>, Box<Vec<Statement>>),
-    FunctionDeclaration(String, Vec<String>, Box<Vec<Statement>>),
+    IndexAssignment(Box<Expression>, Box<Expression>, Box<Expression>),
+    FieldAssignment(Box<Expression>, String, Box<Expression>),
+    IfBlock(Box<Expression>, Vec<Statement>),
+    IfElseBlock(Box<Expression>, Vec<Statement>, Vec<Statement>),
+    While(Box<Expression>, Vec<Statement>),
+    For(
+        Option<Box<Statement>>,
+        Option<Box<Expression>>,
+        Option<Box<Statement>>,
+        Vec<Statement>,
+    ),
+    ForIn(String, Box<Expression>, Vec<Statement>),
+    Break,
+    Continue,
+    FunctionDeclaration(String, Vec<Param>, Vec<Statement>),
+    StructDeclaration(String, Vec<String>),
     Return(Option<Box<Expression>>),
     Print(Box<Expression>),
+    Error,
+}
+
+/// A function parameter: `a`, `b = 2` or `...rest`.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<Expression>,
+    /// Collects the remaining positional arguments into a list. Only the last
+    /// parameter can be a rest parameter.
+    pub rest: bool,
+}
+
+/// A call argument, either positional `f(1)` or named `f(a: 1)`.
+#[derive(Debug, Clone)]
+pub struct Argument {
+    pub name: Option<String>,
+    pub value: Expression,
 }
 
 #[derive(Debug, Clone)]
@@ -34,16 +68,36 @@ pub struct Assignment {
     pub value: Expression,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    /// An integer literal too large for `Int`.
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
 #[derive(Debug, Clone)]
 pub enum ExpressionType {
-    Literal(String),
+    Literal(Literal),
     Variable(String),
     Binary(Box<Expression>, Token, Box<Expression>),
     Unary(Token, Box<Expression>),
-    FunctionCall(String, Box<Vec<Expression>>),
+    Call(Box<Expression>, Vec<Argument>),
+    Function(Vec<Param>, Vec<Statement>),
+    List(Vec<Expression>),
+    Map(Vec<(Expression, Expression)>),
+    Index(Box<Expression>, Box<Expression>),
+    /// `Point { x: 1, y: 2 }`
+    Struct(String, Vec<(String, Expression)>),
+    Field(Box<Expression>, String),
+    Error,
 }
 
 #[derive(Debug, Clone)]
 pub struct Expression {
     pub expression_type: ExpressionType,
+    pub span: Span,
 }
@@ -0,0 +1,205 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::environment::Environment;
+use super::evaluator::{MapKey, RuntimeError, Value, ValueType};
+use super::span::Span;
+
+/// A function implemented in Rust. `span` is the span of the call, for errors.
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: fn(&[Value], Span) -> Result<Value, RuntimeError>,
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "len",
+        arity: 1,
+        function: len,
+    },
+    Builtin {
+        name: "push",
+        arity: 2,
+        function: push,
+    },
+    Builtin {
+        name: "pop",
+        arity: 1,
+        function: pop,
+    },
+    Builtin {
+        name: "keys",
+        arity: 1,
+        function: keys,
+    },
+    Builtin {
+        name: "values",
+        arity: 1,
+        function: values,
+    },
+    Builtin {
+        name: "has",
+        arity: 2,
+        function: has,
+    },
+    Builtin {
+        name: "int",
+        arity: 1,
+        function: int,
+    },
+    Builtin {
+        name: "float",
+        arity: 1,
+        function: float,
+    },
+];
+
+/// Names of every builtin, for the checker.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|builtin| builtin.name)
+}
+
+/// Binds every builtin in `environment`, normally the global scope.
+pub fn define_all(environment: &Rc<RefCell<Environment>>) {
+    for builtin in BUILTINS {
+        environment.borrow_mut().define(
+            builtin.name.to_string(),
+            Value::new(ValueType::Builtin(builtin.clone())),
+        );
+    }
+}
+
+fn invalid_argument(builtin: &str, value: &Value, span: Span) -> RuntimeError {
+    RuntimeError::InvalidArgument {
+        message: format!("`{}` can't be applied to {}", builtin, value.type_name()),
+        span,
+    }
+}
+
+fn len(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    let length = match &args[0].value_type {
+        ValueType::List(list) => list.borrow().len(),
+        ValueType::Map(map) => map.borrow().len(),
+        ValueType::String(value) => value.chars().count(),
+        _ => return Err(invalid_argument("len", &args[0], span)),
+    };
+
+    Ok(Value::new(ValueType::Integer(length as i64)))
+}
+
+/// Appends to a list in place and returns its new length.
+fn push(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &args[0].value_type {
+        ValueType::List(list) => {
+            list.borrow_mut().push(args[1].clone());
+            Ok(Value::new(ValueType::Integer(list.borrow().len() as i64)))
+        }
+        _ => Err(invalid_argument("push", &args[0], span)),
+    }
+}
+
+/// Removes and returns the last element of a list.
+fn pop(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &args[0].value_type {
+        ValueType::List(list) => {
+            list.borrow_mut()
+                .pop()
+                .ok_or_else(|| RuntimeError::InvalidArgument {
+                    message: "`pop` from an empty list".to_string(),
+                    span,
+                })
+        }
+        _ => Err(invalid_argument("pop", &args[0], span)),
+    }
+}
+
+fn list(values: Vec<Value>) -> Value {
+    Value::new(ValueType::List(Rc::new(RefCell::new(values))))
+}
+
+/// The keys of a map, in iteration order.
+fn keys(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &args[0].value_type {
+        ValueType::Map(map) => Ok(list(map.borrow().keys().map(MapKey::to_value).collect())),
+        _ => Err(invalid_argument("keys", &args[0], span)),
+    }
+}
+
+/// The values of a map, in the same order as `keys`.
+fn values(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &args[0].value_type {
+        ValueType::Map(map) => Ok(list(map.borrow().values().cloned().collect())),
+        _ => Err(invalid_argument("values", &args[0], span)),
+    }
+}
+
+/// Whether a map contains a key. Keys that can't be in a map are never found.
+fn has(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    match &args[0].value_type {
+        ValueType::Map(map) => {
+            let found =
+                MapKey::from_value(&args[1]).is_some_and(|key| map.borrow().contains_key(&key));
+            Ok(Value::new(ValueType::Bool(found)))
+        }
+        _ => Err(invalid_argument("has", &args[0], span)),
+    }
+}
+
+/// Converts to an integer, truncating floats toward zero and parsing strings.
+fn int(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    let value = match &args[0].value_type {
+        ValueType::Integer(value) => *value,
+        #[cfg(feature = "bigint")]
+        ValueType::BigInt(_) => return Ok(args[0].clone()),
+        // `i64::MAX as f64` rounds up to 2^63, which is already out of range.
+        ValueType::Float(value) if value.is_finite() && value.abs() < i64::MAX as f64 => {
+            *value as i64
+        }
+        ValueType::Float(value) => {
+            return Err(RuntimeError::InvalidArgument {
+                message: format!("`int` can't convert {:?} to an integer", value),
+                span,
+            })
+        }
+        ValueType::String(text) => {
+            text.trim()
+                .parse()
+                .map_err(|_| RuntimeError::InvalidArgument {
+                    message: format!("`int` can't parse {:?} as an integer", text),
+                    span,
+                })?
+        }
+        _ => return Err(invalid_argument("int", &args[0], span)),
+    };
+
+    Ok(Value::new(ValueType::Integer(value)))
+}
+
+/// Converts to a float, parsing strings.
+fn float(args: &[Value], span: Span) -> Result<Value, RuntimeError> {
+    let value = match &args[0].value_type {
+        ValueType::Integer(value) => *value as f64,
+        ValueType::Float(value) => *value,
+        #[cfg(feature = "bigint")]
+        ValueType::BigInt(value) => num_traits::ToPrimitive::to_f64(value).unwrap_or(f64::NAN),
+        ValueType::String(text) => {
+            text.trim()
+                .parse()
+                .map_err(|_| RuntimeError::InvalidArgument {
+                    message: format!("`float` can't parse {:?} as a float", text),
+                    span,
+                })?
+        }
+        _ => return Err(invalid_argument("float", &args[0], span)),
+    };
+
+    Ok(Value::new(ValueType::Float(value)))
+}
@@ -0,0 +1,726 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::ast::{
+    Argument, Expression, ExpressionType, Literal, Param, Program, Statement, StatementType,
+};
+use super::builtins;
+use super::span::Span;
+use super::token::TokenType;
+use crate::diagnostic::Diagnostic;
+
+/// An error in a program that parsed fine but can't be run.
+#[derive(Debug, Clone)]
+pub struct SemanticError {
+    pub msg: String,
+    pub span: Span,
+}
+
+impl SemanticError {
+    pub fn new(msg: impl Into<String>, span: Span) -> SemanticError {
+        SemanticError {
+            msg: msg.into(),
+            span,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::new(self.msg.clone(), self.span)
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.msg)
+    }
+}
+
+impl std::error::Error for SemanticError {}
+
+/// What the checker knows about a name.
+#[derive(Debug, Clone, PartialEq)]
+enum Binding {
+    /// Declared by a `struct` declaration with these fields, and nothing else
+    /// in the same scope.
+    Struct(Vec<String>),
+    Other,
+}
+
+/// The names of one scope.
+#[derive(Default)]
+struct Scope {
+    /// Names declared by the statements checked so far.
+    declared: HashMap<String, Binding>,
+    /// Names declared anywhere in the scope, including further down.
+    all: HashMap<String, Binding>,
+}
+
+/// Static checks run between parsing and evaluation.
+///
+/// Names are resolved the way the evaluator binds them, in order. Only a
+/// function body can use a name its enclosing scopes declare after it, since
+/// the name is bound by the time the function is called.
+///
+/// Operators are type-checked where the operand types follow from literals,
+/// e.g. `"42" + 1` or `!1`.
+pub struct Checker {
+    function_depth: usize,
+    /// Index of the innermost function's scope. Scopes below it belong to
+    /// code around the function.
+    function_scope: usize,
+    loop_depth: usize,
+    /// Innermost last. The first scope holds the globals.
+    scopes: Vec<Scope>,
+    /// Whether later input can still declare globals, as in the REPL.
+    in_session: bool,
+    errors: Vec<SemanticError>,
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        let builtins = builtins::names()
+            .map(|name| (name.to_string(), Binding::Other))
+            .collect();
+
+        Checker {
+            function_depth: 0,
+            function_scope: 0,
+            loop_depth: 0,
+            scopes: vec![Scope {
+                declared: builtins,
+                all: HashMap::new(),
+            }],
+            in_session: false,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds names that are already defined in the global scope before the
+    /// program runs, like the `args` of a script.
+    pub fn with_globals(mut self, names: impl IntoIterator<Item = String>) -> Checker {
+        for name in names {
+            self.scopes[0].declared.insert(name, Binding::Other);
+        }
+
+        self
+    }
+
+    /// Checks one input of a session, after which more input can declare new
+    /// globals. A function body may then use a name nothing declares yet, as
+    /// long as it is declared by the time the function is called.
+    pub fn in_session(mut self) -> Checker {
+        self.in_session = true;
+
+        self
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checker {
+    pub fn check(&mut self, program: &Program) -> Result<(), Vec<SemanticError>> {
+        self.declare_all(&program.statements);
+        self.check_block(&program.statements);
+
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn check_block(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+
+    /// Checks a `{ ... }` block in a new scope, which starts out with `names`.
+    fn check_scoped(&mut self, names: &[&String], statements: &[Statement]) {
+        let declared = names
+            .iter()
+            .map(|name| (name.to_string(), Binding::Other))
+            .collect();
+        self.scopes.push(Scope {
+            declared,
+            all: HashMap::new(),
+        });
+        self.declare_all(statements);
+        self.check_block(statements);
+        self.scopes.pop();
+    }
+
+    /// Records every name that `statements` bind in the innermost scope, for
+    /// functions declared among them.
+    fn declare_all(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Some((name, binding)) = Self::binding(statement) {
+                let all = &mut self.scope().all;
+                match all.get(name) {
+                    // Which of two declarations is in effect depends on the
+                    // order they run in, so nothing is known about the name.
+                    Some(existing) if *existing != binding => {
+                        all.insert(name.clone(), Binding::Other);
+                    }
+                    Some(_) => {}
+                    None => {
+                        all.insert(name.clone(), binding);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Binds the name `statement` declares, once it has been checked.
+    fn declare(&mut self, statement: &Statement) {
+        if let Some((name, binding)) = Self::binding(statement) {
+            self.scope().declared.insert(name.clone(), binding);
+        }
+    }
+
+    fn binding(statement: &Statement) -> Option<(&String, Binding)> {
+        let (name, binding) = match &statement.statement_type {
+            StatementType::Declaration(declaration) => (&declaration.identif, Binding::Other),
+            StatementType::FunctionDeclaration(name, _, _) => (name, Binding::Other),
+            StatementType::StructDeclaration(name, fields) => {
+                (name, Binding::Struct(fields.clone()))
+            }
+            _ => return None,
+        };
+
+        Some((name, binding))
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes
+            .last_mut()
+            .expect("there is always a global scope")
+    }
+
+    fn resolve(&self, name: &str) -> Option<&Binding> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| {
+                if depth < self.function_scope {
+                    scope.all.get(name).or_else(|| scope.declared.get(name))
+                } else {
+                    scope.declared.get(name)
+                }
+            })
+    }
+
+    /// Whether an unresolved name may still be declared before it is used.
+    fn may_be_declared_later(&self) -> bool {
+        self.in_session && self.function_depth > 0
+    }
+
+    /// Reports `name` if no scope declares it. `kind` is "variable" or
+    /// "function", as in the matching runtime errors.
+    fn check_name(&mut self, name: &str, kind: &str, span: Span) {
+        if self.resolve(name).is_none() && !self.may_be_declared_later() {
+            self.errors.push(SemanticError::new(
+                format!("Undefined {} `{}`", kind, name),
+                span,
+            ));
+        }
+    }
+
+    fn check_statement(&mut self, statement: &Statement) {
+        self.check_statement_body(statement);
+        self.declare(statement);
+    }
+
+    fn check_statement_body(&mut self, statement: &Statement) {
+        match &statement.statement_type {
+            StatementType::Declaration(declaration) => self.check_expression(&declaration.value),
+            StatementType::Assignment(assignment) => {
+                self.check_name(&assignment.identif, "variable", statement.span);
+                self.check_expression(&assignment.value);
+            }
+            StatementType::IndexAssignment(collection, index, value) => {
+                self.check_expression(collection);
+                self.check_expression(index);
+                self.check_expression(value);
+            }
+            StatementType::FieldAssignment(record, _, value) => {
+                self.check_expression(record);
+                self.check_expression(value);
+            }
+            StatementType::FunctionCall(call) => self.check_expression(call),
+            StatementType::Print(expr) => self.check_expression(expr),
+            StatementType::IfBlock(condition, body) => {
+                self.check_expression(condition);
+                self.check_scoped(&[], body);
+            }
+            StatementType::IfElseBlock(condition, if_body, else_body) => {
+                self.check_expression(condition);
+                self.check_scoped(&[], if_body);
+                self.check_scoped(&[], else_body);
+            }
+            StatementType::FunctionDeclaration(name, params, body) => {
+                self.check_function(name, params, body, statement.span);
+            }
+            StatementType::StructDeclaration(name, fields) => {
+                self.check_unique_fields(name, fields.iter(), statement.span);
+            }
+            StatementType::While(condition, body) => {
+                self.check_expression(condition);
+                self.check_loop_body(&[], body);
+            }
+            StatementType::For(init, condition, step, body) => {
+                // Variables declared in the header live for the whole loop.
+                self.scopes.push(Scope::default());
+                if let Some(init) = init {
+                    self.check_statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.check_expression(condition);
+                }
+                if let Some(step) = step {
+                    self.check_statement(step);
+                }
+                self.check_loop_body(&[], body);
+                self.scopes.pop();
+            }
+            StatementType::ForIn(variable, iterable, body) => {
+                self.check_expression(iterable);
+                self.check_loop_body(&[variable], body);
+            }
+            StatementType::Break if self.loop_depth == 0 => {
+                self.errors.push(SemanticError::new(
+                    "`break` outside of a loop",
+                    statement.span,
+                ));
+            }
+            StatementType::Continue if self.loop_depth == 0 => {
+                self.errors.push(SemanticError::new(
+                    "`continue` outside of a loop",
+                    statement.span,
+                ));
+            }
+            StatementType::Return(value) => {
+                if self.function_depth == 0 {
+                    self.errors.push(SemanticError::new(
+                        "`return` outside of a function",
+                        statement.span,
+                    ));
+                }
+                if let Some(value) = value {
+                    self.check_expression(value);
+                }
+            }
+            StatementType::Break | StatementType::Continue | StatementType::Error => {}
+        }
+    }
+
+    fn check_loop_body(&mut self, names: &[&String], body: &[Statement]) {
+        self.loop_depth += 1;
+        self.check_scoped(names, body);
+        self.loop_depth -= 1;
+    }
+
+    fn check_function(&mut self, name: &str, params: &[Param], body: &[Statement], span: Span) {
+        // Parameters, their defaults and the body share one scope.
+        self.scopes.push(Scope::default());
+        let function_scope = std::mem::replace(&mut self.function_scope, self.scopes.len() - 1);
+        self.function_depth += 1;
+
+        let mut seen = HashSet::new();
+        let mut has_default = false;
+        for param in params {
+            if !seen.insert(&param.name) {
+                self.errors.push(SemanticError::new(
+                    format!(
+                        "Duplicate parameter `{}` in function `{}`",
+                        param.name, name
+                    ),
+                    span,
+                ));
+            }
+
+            match &param.default {
+                // Defaults can use the parameters before them.
+                Some(default) => {
+                    has_default = true;
+                    self.check_expression(default);
+                }
+                // Positional arguments fill parameters in order, so a required
+                // parameter after an optional one could never be skipped.
+                None if has_default && !param.rest => {
+                    self.errors.push(SemanticError::new(
+                        format!(
+                            "Parameter `{}` in function `{}` needs a default like the ones before it",
+                            param.name, name
+                        ),
+                        span,
+                    ));
+                }
+                None => {}
+            }
+            self.scope()
+                .declared
+                .insert(param.name.clone(), Binding::Other);
+        }
+
+        // `break` can't jump out of a function into an enclosing loop.
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.declare_all(body);
+        self.check_block(body);
+        self.loop_depth = loop_depth;
+
+        self.function_depth -= 1;
+        self.function_scope = function_scope;
+        self.scopes.pop();
+    }
+
+    fn check_unique_fields<'a>(
+        &mut self,
+        name: &str,
+        fields: impl Iterator<Item = &'a String>,
+        span: Span,
+    ) {
+        let mut seen = HashSet::new();
+        for field in fields {
+            if !seen.insert(field) {
+                self.errors.push(SemanticError::new(
+                    format!("Duplicate field `{}` in struct `{}`", field, name),
+                    span,
+                ));
+            }
+        }
+    }
+
+    /// Checks a struct literal against its declaration, when it is known.
+    fn check_struct_fields(&mut self, name: &str, fields: &[(String, Expression)], span: Span) {
+        let declared = match self.resolve(name) {
+            Some(Binding::Struct(declared)) => declared,
+            Some(Binding::Other) => return,
+            None if self.may_be_declared_later() => return,
+            None => {
+                self.errors.push(SemanticError::new(
+                    format!("Unknown struct `{}`", name),
+                    span,
+                ));
+                return;
+            }
+        };
+
+        let missing = declared
+            .iter()
+            .filter(|field| !fields.iter().any(|(given, _)| given == *field))
+            .map(|field| format!("Missing field `{}` in `{}`", field, name));
+        let unknown = fields
+            .iter()
+            .filter(|(field, _)| !declared.contains(field))
+            .map(|(field, _)| format!("No field `{}` on `{}`", field, name));
+        let errors: Vec<_> = missing
+            .chain(unknown)
+            .map(|msg| SemanticError::new(msg, span))
+            .collect();
+        self.errors.extend(errors);
+    }
+
+    fn check_arguments(&mut self, args: &[Argument], span: Span) {
+        let mut seen = HashSet::new();
+        for arg in args {
+            if let Some(name) = &arg.name {
+                if !seen.insert(name) {
+                    self.errors.push(SemanticError::new(
+                        format!("Argument `{}` is given more than once", name),
+                        span,
+                    ));
+                }
+            }
+            self.check_expression(&arg.value);
+        }
+    }
+
+    fn check_expression(&mut self, expr: &Expression) {
+        self.check_typed(expr);
+    }
+
+    /// Checks `expr` and returns the type it evaluates to, when that is known
+    /// without running it. Types are named as in runtime errors.
+    fn check_typed(&mut self, expr: &Expression) -> Option<&'static str> {
+        match &expr.expression_type {
+            ExpressionType::Binary(left, operator, right) => {
+                let left = self.check_typed(left);
+                let right = self.check_typed(right);
+                let (left, right) = (left?, right?);
+                let result = binary_type(operator.token_type, left, right);
+                if result.is_none() {
+                    self.errors.push(SemanticError::new(
+                        format!(
+                            "Unsupported operand types for `{}`: {} and {}",
+                            operator.value, left, right
+                        ),
+                        expr.span,
+                    ));
+                }
+                result
+            }
+            ExpressionType::Unary(operator, operand) => {
+                let operand = self.check_typed(operand)?;
+                let result = unary_type(operator.token_type, operand);
+                if result.is_none() {
+                    self.errors.push(SemanticError::new(
+                        format!(
+                            "Unsupported operand type for `{}`: {}",
+                            operator.value, operand
+                        ),
+                        expr.span,
+                    ));
+                }
+                result
+            }
+            ExpressionType::Index(collection, index) => {
+                self.check_expression(collection);
+                self.check_expression(index);
+                None
+            }
+            ExpressionType::List(elements) => {
+                elements
+                    .iter()
+                    .for_each(|element| self.check_expression(element));
+                Some("List")
+            }
+            ExpressionType::Map(entries) => {
+                for (key, value) in entries {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+                Some("Map")
+            }
+            ExpressionType::Struct(name, fields) => {
+                self.check_unique_fields(name, fields.iter().map(|(field, _)| field), expr.span);
+                self.check_struct_fields(name, fields, expr.span);
+                fields
+                    .iter()
+                    .for_each(|(_, value)| self.check_expression(value));
+                Some("Struct")
+            }
+            ExpressionType::Field(record, _) => {
+                self.check_expression(record);
+                None
+            }
+            ExpressionType::Call(callee, args) => {
+                match &callee.expression_type {
+                    ExpressionType::Variable(name) => {
+                        self.check_name(name, "function", callee.span)
+                    }
+                    _ => self.check_expression(callee),
+                }
+                self.check_arguments(args, expr.span);
+                None
+            }
+            ExpressionType::Function(params, body) => {
+                self.check_function("<anonymous>", params, body, expr.span);
+                Some("Function")
+            }
+            ExpressionType::Variable(name) => {
+                self.check_name(name, "variable", expr.span);
+                None
+            }
+            ExpressionType::Literal(literal) => Some(match literal {
+                Literal::Int(_) => "Integer",
+                #[cfg(feature = "bigint")]
+                Literal::BigInt(_) => "Integer",
+                Literal::Float(_) => "Float",
+                Literal::Str(_) => "String",
+                Literal::Bool(_) => "Bool",
+            }),
+            ExpressionType::Error => None,
+        }
+    }
+}
+
+/// The type of `left operator right`, or `None` if the evaluator rejects the
+/// operands. Mirrors the evaluator and `arithmetic::apply`.
+fn binary_type(operator: TokenType, left: &str, right: &str) -> Option<&'static str> {
+    let numbers = is_number(left) && is_number(right);
+    let strings = left == "String" && right == "String";
+
+    match operator {
+        TokenType::Eq | TokenType::NotEq | TokenType::And | TokenType::Or => Some("Bool"),
+        TokenType::Gt | TokenType::Lt | TokenType::GtEq | TokenType::LtEq if numbers || strings => {
+            Some("Bool")
+        }
+        TokenType::Plus if strings => Some("String"),
+        TokenType::Plus
+        | TokenType::Minus
+        | TokenType::Asterisk
+        | TokenType::Slash
+        | TokenType::Percent
+        | TokenType::Power
+            if numbers =>
+        {
+            if left == "Float" || right == "Float" {
+                Some("Float")
+            } else {
+                Some("Integer")
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The type of a unary operator applied to `operand`, or `None` if the
+/// evaluator rejects it.
+fn unary_type(operator: TokenType, operand: &'static str) -> Option<&'static str> {
+    match operator {
+        TokenType::Bang if operand == "Bool" => Some("Bool"),
+        TokenType::Minus | TokenType::Plus if is_number(operand) => Some(operand),
+        _ => None,
+    }
+}
+
+fn is_number(type_name: &str) -> bool {
+    type_name == "Integer" || type_name == "Float"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::Lexer;
+    use crate::compiler::parser::Parser;
+
+    fn errors(checker: Checker, source: &str) -> Vec<String> {
+        let tokens = Lexer::new(source).lex().expect("source should lex");
+        let program = Parser::new(tokens).parse().expect("source should parse");
+        let mut checker = checker;
+        match checker.check(&program) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.into_iter().map(|error| error.msg).collect(),
+        }
+    }
+
+    #[test]
+    fn resolves_names_through_scopes() {
+        let source = "
+            func first() { return second(len([])); }
+            func second(n, m = n, ...rest) { return n + m + len(rest); }
+            for (let i = 0; i < 1; i = i + 1) { print first() + i; }
+            for x in [1] { let y = x; print y; }
+        ";
+
+        assert!(errors(Checker::new(), source).is_empty());
+    }
+
+    #[test]
+    fn reports_undefined_names() {
+        let source = "
+            if (true) { let inner = 1; }
+            print inner;
+            missing(1);
+            undeclared = 2;
+        ";
+
+        assert_eq!(
+            errors(Checker::new(), source),
+            [
+                "Undefined variable `inner`",
+                "Undefined function `missing`",
+                "Undefined variable `undeclared`",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_struct_literals() {
+        let source = "
+            struct Point { x, y }
+            print Point { x: 1 };
+            print Point { x: 1, y: 2, z: 3 };
+            print Line { a: 1 };
+        ";
+
+        assert_eq!(
+            errors(Checker::new(), source),
+            [
+                "Missing field `y` in `Point`",
+                "No field `z` on `Point`",
+                "Unknown struct `Line`",
+            ]
+        );
+    }
+
+    #[test]
+    fn lets_session_functions_use_later_globals() {
+        let source = "func f() { return g(); } print known;";
+
+        assert!(errors(
+            Checker::new()
+                .with_globals(["known".to_string()])
+                .in_session(),
+            source
+        )
+        .is_empty());
+        assert_eq!(
+            errors(Checker::new(), source),
+            ["Undefined function `g`", "Undefined variable `known`"]
+        );
+    }
+
+    #[test]
+    fn resolves_statements_in_order() {
+        let source = "
+            print x;
+            let x = 1;
+            f();
+            func f() {}
+            if (true) { print y; let y = 2; }
+            func g(a = b, b = 1) { return a; }
+        ";
+
+        assert_eq!(
+            errors(Checker::new(), source),
+            [
+                "Undefined variable `x`",
+                "Undefined function `f`",
+                "Undefined variable `y`",
+                "Undefined variable `b`",
+            ]
+        );
+    }
+
+    #[test]
+    fn lets_functions_use_names_declared_after_them() {
+        let source = "
+            func count(n) { return helper(n) + total; }
+            let total = 0;
+            func helper(n) { return n; }
+            print count(1);
+            let recurse = fn(n) { return recurse(n - 1); };
+        ";
+
+        assert!(errors(Checker::new(), source).is_empty());
+    }
+
+    #[test]
+    fn reports_operators_on_values_of_the_wrong_type() {
+        let source = "
+            print \"42\" + 1;
+            print !1;
+            print -[1] < 2;
+            print (1 + 2.5) * \"x\";
+            print \"a\" + \"b\" == \"ab\" && 1 < 2.5 && !true;
+            print len([]) + 1;
+        ";
+
+        assert_eq!(
+            errors(Checker::new(), source),
+            [
+                "Unsupported operand types for `+`: String and Integer",
+                "Unsupported operand type for `!`: Integer",
+                "Unsupported operand type for `-`: List",
+                "Unsupported operand types for `*`: Float and String",
+            ]
+        );
+    }
+}
@@ -1,9 +1,18 @@
 use super::{
+    arithmetic,
     ast::{self, Expression, Program, Statement},
+    builtins::{self, Builtin},
+    environment::Environment,
+    span::Span,
     token::TokenType,
 };
+use crate::diagnostic::Diagnostic;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct Value {
@@ -13,8 +22,222 @@ pub struct Value {
 #[derive(Debug, Clone)]
 pub enum ValueType {
     Integer(i64),
+    Float(f64),
+    /// Only integers outside the `i64` range are stored here.
+    #[cfg(feature = "bigint")]
+    BigInt(num_bigint::BigInt),
     String(String),
     Bool(bool),
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    /// Lists are shared by reference, so `push(xs, 1)` is visible through every alias.
+    List(Rc<RefCell<Vec<Value>>>),
+    /// Maps are shared by reference like lists and iterate in key order.
+    Map(Rc<RefCell<BTreeMap<MapKey, Value>>>),
+    /// What a `struct` declaration binds its name to.
+    StructType(Rc<StructType>),
+    /// Struct instances are shared by reference like lists.
+    Struct(Rc<RefCell<Record>>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StructType {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// An instance of a struct, with values in the order the fields were declared.
+#[derive(Debug)]
+pub struct Record {
+    pub struct_type: Rc<StructType>,
+    pub values: Vec<Value>,
+}
+
+impl Record {
+    fn position(&self, field: &str) -> Option<usize> {
+        self.struct_type
+            .fields
+            .iter()
+            .position(|name| name == field)
+    }
+}
+
+/// The values that can be used as map keys.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapKey {
+    Integer(i64),
+    String(String),
+}
+
+/// Keys are shown the way they'd be written in a map literal.
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapKey::Integer(value) => write!(f, "{}", value),
+            MapKey::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value) -> Option<MapKey> {
+        match &value.value_type {
+            ValueType::Integer(value) => Some(MapKey::Integer(*value)),
+            ValueType::String(value) => Some(MapKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Integer(value) => Value::new(ValueType::Integer(*value)),
+            MapKey::String(value) => Value::new(ValueType::String(value.clone())),
+        }
+    }
+}
+
+/// A function value together with the scope it was defined in.
+pub struct Function {
+    pub name: Option<String>,
+    pub params: Vec<ast::Param>,
+    pub body: Vec<Statement>,
+    pub closure: Rc<RefCell<Environment>>,
+}
+
+impl Function {
+    /// Name used in error messages.
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous>")
+    }
+}
+
+impl fmt::Debug for Function {
+    // The closure may contain this very function, so it isn't printed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Containers compare by contents. Lists, maps and structs can contain
+/// themselves, so a pair that is already being compared further up counts as
+/// equal instead of recursing forever.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other, &mut Vec::new())
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_value(f, &mut Vec::new())
+    }
+}
+
+/// Address of a shared container, to recognise it again while recursing.
+fn address<T>(container: &Rc<T>) -> *const () {
+    Rc::as_ptr(container) as *const ()
+}
+
+impl Value {
+    /// Formats the value, showing a container that contains itself as `[...]`,
+    /// `{...}` or `Name {...}` the second time it is reached. `seen` holds the
+    /// containers currently being printed.
+    fn fmt_value(&self, f: &mut fmt::Formatter, seen: &mut Vec<*const ()>) -> fmt::Result {
+        let container = match &self.value_type {
+            ValueType::List(list) => Some(address(list)),
+            ValueType::Map(map) => Some(address(map)),
+            ValueType::Struct(record) => Some(address(record)),
+            _ => None,
+        };
+        if let Some(container) = container {
+            if seen.contains(&container) {
+                return match &self.value_type {
+                    ValueType::List(_) => write!(f, "[...]"),
+                    ValueType::Struct(record) => {
+                        write!(f, "{} {{...}}", record.borrow().struct_type.name)
+                    }
+                    _ => write!(f, "{{...}}"),
+                };
+            }
+            seen.push(container);
+        }
+
+        let result = self.fmt_contents(f, seen);
+        if container.is_some() {
+            seen.pop();
+        }
+
+        result
+    }
+
+    fn fmt_contents(&self, f: &mut fmt::Formatter, seen: &mut Vec<*const ()>) -> fmt::Result {
+        match &self.value_type {
+            ValueType::Integer(value) => write!(f, "{}", value),
+            // `{:?}` keeps the `.0` on whole floats so `3.0` doesn't print as `3`.
+            ValueType::Float(value) => write!(f, "{:?}", value),
+            #[cfg(feature = "bigint")]
+            ValueType::BigInt(value) => write!(f, "{}", value),
+            ValueType::String(value) => write!(f, "{}", value),
+            ValueType::Bool(value) => write!(f, "{}", value),
+            ValueType::Function(function) => match &function.name {
+                Some(name) => write!(f, "<func {}>", name),
+                None => write!(f, "<func>"),
+            },
+            ValueType::Builtin(builtin) => write!(f, "<builtin {}>", builtin.name),
+            ValueType::List(list) => {
+                write!(f, "[")?;
+                for (index, item) in list.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_nested(f, seen)?;
+                }
+                write!(f, "]")
+            }
+            ValueType::Map(map) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in map.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", key)?;
+                    write!(f, ": ")?;
+                    value.fmt_nested(f, seen)?;
+                }
+                write!(f, "}}")
+            }
+            ValueType::StructType(struct_type) => write!(f, "<struct {}>", struct_type.name),
+            ValueType::Struct(record) => {
+                let record = record.borrow();
+                write!(f, "{} {{", record.struct_type.name)?;
+                for (index, (field, value)) in record
+                    .struct_type
+                    .fields
+                    .iter()
+                    .zip(&record.values)
+                    .enumerate()
+                {
+                    write!(f, "{}{}: ", if index > 0 { ", " } else { " " }, field)?;
+                    value.fmt_nested(f, seen)?;
+                }
+                if record.values.is_empty() {
+                    write!(f, "}}")
+                } else {
+                    write!(f, " }}")
+                }
+            }
+        }
+    }
 }
 
 impl Value {
@@ -22,195 +245,1331 @@ impl Value {
         Self { value_type }
     }
 
-    pub fn into_bool(&self) -> bool {
+    pub fn is_truthy(&self) -> bool {
         match &self.value_type {
-            ValueType::Bool(value) => value.clone(),
-            ValueType::Integer(value) => value.clone() != 0,
+            ValueType::Bool(value) => *value,
+            ValueType::Integer(value) => *value != 0,
+            ValueType::Float(value) => *value != 0.0,
+            #[cfg(feature = "bigint")]
+            ValueType::BigInt(_) => true,
             ValueType::String(value) => !value.is_empty(),
+            ValueType::Function(_) | ValueType::Builtin(_) => true,
+            ValueType::List(list) => !list.borrow().is_empty(),
+            ValueType::Map(map) => !map.borrow().is_empty(),
+            ValueType::StructType(_) | ValueType::Struct(_) => true,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self.value_type {
+            ValueType::Integer(_) => "Integer",
+            ValueType::Float(_) => "Float",
+            #[cfg(feature = "bigint")]
+            ValueType::BigInt(_) => "Integer",
+            ValueType::String(_) => "String",
+            ValueType::Bool(_) => "Bool",
+            ValueType::Function(_) | ValueType::Builtin(_) => "Function",
+            ValueType::List(_) => "List",
+            ValueType::Map(_) => "Map",
+            ValueType::StructType(_) => "StructType",
+            ValueType::Struct(_) => "Struct",
+        }
+    }
+
+    /// Formats a value inside a container, where strings are shown quoted.
+    fn fmt_nested(&self, f: &mut fmt::Formatter, seen: &mut Vec<*const ()>) -> fmt::Result {
+        match &self.value_type {
+            ValueType::String(value) => write!(f, "{:?}", value),
+            _ => self.fmt_value(f, seen),
+        }
+    }
+
+    /// Structural equality. `comparing` holds the pairs of containers
+    /// currently being compared.
+    fn equals(&self, other: &Value, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+        match (&self.value_type, &other.value_type) {
+            (ValueType::Integer(left), ValueType::Integer(right)) => left == right,
+            (ValueType::Float(left), ValueType::Float(right)) => left == right,
+            #[cfg(feature = "bigint")]
+            (ValueType::BigInt(left), ValueType::BigInt(right)) => left == right,
+            (ValueType::String(left), ValueType::String(right)) => left == right,
+            (ValueType::Bool(left), ValueType::Bool(right)) => left == right,
+            (ValueType::Function(left), ValueType::Function(right)) => left == right,
+            (ValueType::Builtin(left), ValueType::Builtin(right)) => left == right,
+            (ValueType::StructType(left), ValueType::StructType(right)) => left == right,
+            (ValueType::List(left), ValueType::List(right)) => {
+                Self::equal_shared(left, right, comparing, |left, right, comparing| {
+                    left.len() == right.len()
+                        && left
+                            .iter()
+                            .zip(right)
+                            .all(|(left, right)| left.equals(right, comparing))
+                })
+            }
+            (ValueType::Map(left), ValueType::Map(right)) => {
+                Self::equal_shared(left, right, comparing, |left, right, comparing| {
+                    left.len() == right.len()
+                        && left.iter().zip(right).all(|(left, right)| {
+                            left.0 == right.0 && left.1.equals(right.1, comparing)
+                        })
+                })
+            }
+            (ValueType::Struct(left), ValueType::Struct(right)) => {
+                Self::equal_shared(left, right, comparing, |left, right, comparing| {
+                    left.struct_type == right.struct_type
+                        && left
+                            .values
+                            .iter()
+                            .zip(&right.values)
+                            .all(|(left, right)| left.equals(right, comparing))
+                })
+            }
+            _ => false,
+        }
+    }
+
+    /// Compares the contents of two shared containers with `equal`, unless
+    /// they are the same container or that pair is already being compared.
+    fn equal_shared<T>(
+        left: &Rc<RefCell<T>>,
+        right: &Rc<RefCell<T>>,
+        comparing: &mut Vec<(*const (), *const ())>,
+        equal: impl FnOnce(&T, &T, &mut Vec<(*const (), *const ())>) -> bool,
+    ) -> bool {
+        let pair = (address(left), address(right));
+        if pair.0 == pair.1 || comparing.contains(&pair) {
+            return true;
+        }
+
+        comparing.push(pair);
+        let result = equal(&left.borrow(), &right.borrow(), comparing);
+        comparing.pop();
+
+        result
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RuntimeError {
+    TypeMismatch {
+        operator: String,
+        operands: Vec<&'static str>,
+        span: Span,
+    },
+    UndefinedVariable {
+        name: String,
+        span: Span,
+    },
+    UndefinedFunction {
+        name: String,
+        span: Span,
+    },
+    DivisionByZero {
+        span: Span,
+    },
+    IntegerOverflow {
+        operator: String,
+        span: Span,
+    },
+    NegativeExponent {
+        exponent: i64,
+        span: Span,
+    },
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    MissingArgument {
+        function: String,
+        name: String,
+        span: Span,
+    },
+    UnknownArgument {
+        function: String,
+        name: String,
+        span: Span,
+    },
+    DuplicateArgument {
+        function: String,
+        name: String,
+        span: Span,
+    },
+    NotIterable {
+        type_name: &'static str,
+        span: Span,
+    },
+    NotCallable {
+        type_name: &'static str,
+        span: Span,
+    },
+    /// A big integer used where only a 64-bit one fits, e.g. as an index.
+    IntegerTooLarge {
+        purpose: &'static str,
+        span: Span,
+    },
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+        span: Span,
+    },
+    KeyNotFound {
+        key: String,
+        span: Span,
+    },
+    UnknownStruct {
+        name: String,
+        span: Span,
+    },
+    MissingField {
+        struct_name: String,
+        field: String,
+        span: Span,
+    },
+    DuplicateField {
+        struct_name: String,
+        field: String,
+        span: Span,
+    },
+    /// A field that doesn't exist on a struct, or any field on a non-struct.
+    UnknownField {
+        type_name: String,
+        field: String,
+        span: Span,
+    },
+    InvalidArgument {
+        message: String,
+        span: Span,
+    },
+    Unsupported {
+        what: String,
+        span: Span,
+    },
+    /// A `break` or `continue` that isn't inside a loop of its own function.
+    OutsideLoop {
+        keyword: &'static str,
+        span: Span,
+    },
+    /// Writing `print` output failed, e.g. because stdout was closed.
+    Output {
+        kind: io::ErrorKind,
+        span: Span,
+    },
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::TypeMismatch { span, .. }
+            | RuntimeError::UndefinedVariable { span, .. }
+            | RuntimeError::UndefinedFunction { span, .. }
+            | RuntimeError::DivisionByZero { span }
+            | RuntimeError::IntegerOverflow { span, .. }
+            | RuntimeError::NegativeExponent { span, .. }
+            | RuntimeError::ArityMismatch { span, .. }
+            | RuntimeError::MissingArgument { span, .. }
+            | RuntimeError::UnknownArgument { span, .. }
+            | RuntimeError::DuplicateArgument { span, .. }
+            | RuntimeError::NotIterable { span, .. }
+            | RuntimeError::NotCallable { span, .. }
+            | RuntimeError::IntegerTooLarge { span, .. }
+            | RuntimeError::IndexOutOfBounds { span, .. }
+            | RuntimeError::KeyNotFound { span, .. }
+            | RuntimeError::UnknownStruct { span, .. }
+            | RuntimeError::MissingField { span, .. }
+            | RuntimeError::DuplicateField { span, .. }
+            | RuntimeError::UnknownField { span, .. }
+            | RuntimeError::InvalidArgument { span, .. }
+            | RuntimeError::Unsupported { span, .. }
+            | RuntimeError::OutsideLoop { span, .. }
+            | RuntimeError::Output { span, .. } => *span,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::new(self.to_string(), self.span());
+        match self {
+            RuntimeError::UndefinedVariable { name, .. } => {
+                diagnostic.with_help(format!("declare it first with `let {} = ...;`", name))
+            }
+            RuntimeError::UndefinedFunction { name, .. } => diagnostic.with_help(format!(
+                "declare it first with `func {}(...) {{ ... }}`",
+                name
+            )),
+            RuntimeError::UnknownStruct { name, .. } => {
+                diagnostic.with_help(format!("declare it first with `struct {} {{ ... }}`", name))
+            }
+            RuntimeError::IntegerOverflow { .. } if cfg!(not(feature = "bigint")) => {
+                diagnostic.with_help("integers are 64-bit unless built with `--features bigint`")
+            }
+            RuntimeError::NegativeExponent { .. } => {
+                diagnostic.with_help("use a float base for fractional results, e.g. `2.0 ** -1`")
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::TypeMismatch {
+                operator, operands, ..
+            } => write!(
+                f,
+                "Unsupported operand {} for `{}`: {}",
+                if operands.len() == 1 { "type" } else { "types" },
+                operator,
+                operands.join(" and ")
+            ),
+            RuntimeError::UndefinedVariable { name, .. } => {
+                write!(f, "Undefined variable `{}`", name)
+            }
+            RuntimeError::UndefinedFunction { name, .. } => {
+                write!(f, "Undefined function `{}`", name)
+            }
+            RuntimeError::DivisionByZero { .. } => write!(f, "Division by zero"),
+            RuntimeError::IntegerOverflow { operator, .. } => {
+                write!(f, "Integer overflow in `{}`", operator)
+            }
+            RuntimeError::NegativeExponent { exponent, .. } => {
+                write!(f, "Negative exponent {} in integer `**`", exponent)
+            }
+            RuntimeError::ArityMismatch {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "Function `{}` takes {} argument(s) but {} were given",
+                name, expected, found
+            ),
+            RuntimeError::MissingArgument { function, name, .. } => {
+                write!(f, "Missing argument `{}` in call to `{}`", name, function)
+            }
+            RuntimeError::UnknownArgument { function, name, .. } => {
+                write!(f, "Function `{}` has no parameter `{}`", function, name)
+            }
+            RuntimeError::DuplicateArgument { function, name, .. } => write!(
+                f,
+                "Argument `{}` is given more than once in call to `{}`",
+                name, function
+            ),
+            RuntimeError::NotIterable { type_name, .. } => {
+                write!(f, "Cannot iterate over a value of type {}", type_name)
+            }
+            RuntimeError::NotCallable { type_name, .. } => {
+                write!(f, "Cannot call a value of type {}", type_name)
+            }
+            RuntimeError::IntegerTooLarge { purpose, .. } => {
+                write!(f, "Integer is too large to use as {}", purpose)
+            }
+            RuntimeError::IndexOutOfBounds { index, length, .. } => write!(
+                f,
+                "Index {} is out of bounds for a list of length {}",
+                index, length
+            ),
+            RuntimeError::KeyNotFound { key, .. } => write!(f, "Key {} not found in map", key),
+            RuntimeError::UnknownStruct { name, .. } => write!(f, "Unknown struct `{}`", name),
+            RuntimeError::MissingField {
+                struct_name, field, ..
+            } => write!(f, "Missing field `{}` in `{}`", field, struct_name),
+            RuntimeError::DuplicateField {
+                struct_name, field, ..
+            } => write!(f, "Duplicate field `{}` in struct `{}`", field, struct_name),
+            RuntimeError::UnknownField {
+                type_name, field, ..
+            } => write!(f, "No field `{}` on {}", field, type_name),
+            RuntimeError::InvalidArgument { message, .. } => write!(f, "{}", message),
+            RuntimeError::Unsupported { what, .. } => write!(f, "Cannot evaluate {}", what),
+            RuntimeError::OutsideLoop { keyword, .. } => {
+                write!(f, "`{}` outside of a loop", keyword)
+            }
+            RuntimeError::Output { kind, .. } => write!(f, "Could not write output: {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// How execution continues after a statement.
+enum ControlFlow {
+    Normal,
+    Break(Span),
+    Continue(Span),
+    Return(Value),
+}
+
+impl ControlFlow {
+    /// Ends a function body or a program, where only `return` may leave
+    /// early. Returns the value of a `return`, if there was one.
+    fn finish(self) -> Result<Option<Value>, RuntimeError> {
+        match self {
+            ControlFlow::Normal => Ok(None),
+            ControlFlow::Return(value) => Ok(Some(value)),
+            ControlFlow::Break(span) => Err(RuntimeError::OutsideLoop {
+                keyword: "break",
+                span,
+            }),
+            ControlFlow::Continue(span) => Err(RuntimeError::OutsideLoop {
+                keyword: "continue",
+                span,
+            }),
         }
     }
 }
 
 pub struct Evaluator {
     pub program: Program,
-    pub variable_stack: Vec<HashMap<String, Value>>,
-    pub functions: HashMap<String, Statement>,
+    /// The innermost scope of the code currently being evaluated.
+    pub environment: Rc<RefCell<Environment>>,
+    pub globals: Rc<RefCell<Environment>>,
 }
 
 impl Evaluator {
     pub fn new(program: Program) -> Self {
+        let globals = Environment::new();
+        builtins::define_all(&globals);
         Self {
             program,
-            variable_stack: vec![HashMap::new()],
-            functions: HashMap::new(),
+            environment: globals.clone(),
+            globals,
         }
     }
 
-    pub fn evaluate(&mut self) {
+    pub fn evaluate(&mut self) -> Result<(), RuntimeError> {
         let statements = self.program.statements.clone();
+        self.evaluate_block(&statements)?.finish()?;
+
+        Ok(())
+    }
+
+    /// Runs another program in the global scope, so its bindings stay around
+    /// for whatever runs next. This is how the REPL keeps state between inputs.
+    pub fn execute(&mut self, program: &Program) -> Result<(), RuntimeError> {
+        self.evaluate_block(&program.statements)?.finish()?;
+
+        Ok(())
+    }
+
+    /// Evaluates statements in order, stopping early when one of them
+    /// breaks the normal flow of control.
+    fn evaluate_block(&mut self, statements: &[Statement]) -> Result<ControlFlow, RuntimeError> {
         for statement in statements {
-            self.evaluate_statement(&statement);
+            match self.evaluate_statement(statement)? {
+                ControlFlow::Normal => {}
+                flow => return Ok(flow),
+            }
         }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    /// Evaluates statements inside `environment`, restoring the current
+    /// scope afterwards.
+    fn evaluate_in(
+        &mut self,
+        statements: &[Statement],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<ControlFlow, RuntimeError> {
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.evaluate_block(statements);
+        self.environment = previous;
+
+        result
+    }
+
+    /// Evaluates a `{ ... }` block in a fresh scope nested in the current one.
+    fn evaluate_scoped(&mut self, statements: &[Statement]) -> Result<ControlFlow, RuntimeError> {
+        let environment = Environment::with_parent(self.environment.clone());
+        self.evaluate_in(statements, environment)
     }
 
-    fn evaluate_statement(&mut self, statement: &Statement) {
+    fn evaluate_statement(&mut self, statement: &Statement) -> Result<ControlFlow, RuntimeError> {
         match &statement.statement_type {
             ast::StatementType::Declaration(ref declaration) => {
-                let value = self.evaluate_expression(&declaration.value);
-                self.variable_stack
-                    .last_mut()
-                    .unwrap()
-                    .insert(declaration.identif.clone(), value);
+                let value = self.evaluate_expression(&declaration.value)?;
+                self.environment
+                    .borrow_mut()
+                    .define(declaration.identif.clone(), value);
             }
             ast::StatementType::Assignment(ref assignment) => {
-                let value = self.evaluate_expression(&assignment.value);
-                self.variable_stack
-                    .last_mut()
-                    .unwrap()
-                    .insert(assignment.identif.clone(), value);
+                let value = self.evaluate_expression(&assignment.value)?;
+                if !self
+                    .environment
+                    .borrow_mut()
+                    .assign(&assignment.identif, value)
+                {
+                    return Err(RuntimeError::UndefinedVariable {
+                        name: assignment.identif.clone(),
+                        span: statement.span,
+                    });
+                }
             }
-            ast::StatementType::Print(ref expr) => {
-                let value = self.evaluate_expression(expr);
-                match value.value_type {
-                    ValueType::Integer(value) => println!("{}", value),
-                    ValueType::String(value) => println!("{}", value),
-                    ValueType::Bool(value) => println!("{}", value),
+            ast::StatementType::IndexAssignment(collection, index, value) => {
+                let collection = self.evaluate_expression(collection)?;
+                let position = self.evaluate_expression(index)?;
+                let value = self.evaluate_expression(value)?;
+
+                match collection.value_type {
+                    ValueType::List(list) => {
+                        let position = Self::list_position(&list.borrow(), &position, index.span)?;
+                        list.borrow_mut()[position] = value;
+                    }
+                    ValueType::Map(map) => {
+                        let key = Self::map_key(&position, index.span)?;
+                        map.borrow_mut().insert(key, value);
+                    }
+                    _ => {
+                        return Err(RuntimeError::TypeMismatch {
+                            operator: "[]=".to_string(),
+                            operands: vec![collection.type_name(), position.type_name()],
+                            span: statement.span,
+                        })
+                    }
                 }
             }
-            ast::StatementType::FunctionDeclaration(name, _, _) => {
-                self.functions.insert(name.clone(), statement.clone());
+            ast::StatementType::FieldAssignment(record, field, value) => {
+                let target = self.evaluate_expression(record)?;
+                let value = self.evaluate_expression(value)?;
+                let record = Self::record(&target, field, statement.span)?;
+                let mut record = record.borrow_mut();
+                let position = Self::field_position(&record, field, statement.span)?;
+                record.values[position] = value;
+            }
+            ast::StatementType::StructDeclaration(name, fields) => {
+                let struct_type = StructType {
+                    name: name.clone(),
+                    fields: fields.clone(),
+                };
+                self.environment.borrow_mut().define(
+                    name.clone(),
+                    Value::new(ValueType::StructType(Rc::new(struct_type))),
+                );
+            }
+            ast::StatementType::Print(ref expr) => {
+                let value = self.evaluate_expression(expr)?;
+                writeln!(io::stdout().lock(), "{}", value).map_err(|error| {
+                    RuntimeError::Output {
+                        kind: error.kind(),
+                        span: statement.span,
+                    }
+                })?;
+            }
+            ast::StatementType::FunctionDeclaration(name, params, body) => {
+                // The closure is the scope the function is declared in, which
+                // also lets the function call itself by name.
+                let function = Function {
+                    name: Some(name.clone()),
+                    params: params.clone(),
+                    body: body.clone(),
+                    closure: self.environment.clone(),
+                };
+                self.environment.borrow_mut().define(
+                    name.clone(),
+                    Value::new(ValueType::Function(Rc::new(function))),
+                );
+            }
+            ast::StatementType::Return(expr) => {
+                let value = match expr {
+                    Some(expr) => self.evaluate_expression(expr)?,
+                    None => Value::new(ValueType::Integer(0)),
+                };
+                return Ok(ControlFlow::Return(value));
             }
-            ast::StatementType::Return(_) => {}
             ast::StatementType::IfBlock(ref expr, body) => {
-                let value = self.evaluate_expression(expr).into_bool();
+                let value = self.evaluate_expression(expr)?.is_truthy();
                 if value {
-                    for statement in body.clone().into_iter() {
-                        self.evaluate_statement(&statement);
-                    }
+                    return self.evaluate_scoped(body);
                 }
             }
             ast::StatementType::IfElseBlock(expr, if_body, else_body) => {
-                let value = self.evaluate_expression(expr).into_bool();
+                let value = self.evaluate_expression(expr)?.is_truthy();
 
                 if value {
-                    for statement in if_body.clone().into_iter() {
-                        self.evaluate_statement(&statement);
-                    }
+                    return self.evaluate_scoped(if_body);
                 } else {
-                    for statement in else_body.clone().into_iter() {
-                        self.evaluate_statement(&statement);
+                    return self.evaluate_scoped(else_body);
+                }
+            }
+            ast::StatementType::While(condition, body) => {
+                while self.evaluate_expression(condition)?.is_truthy() {
+                    match self.evaluate_scoped(body)? {
+                        ControlFlow::Break(_) => break,
+                        ControlFlow::Normal | ControlFlow::Continue(_) => {}
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                    }
+                }
+            }
+            ast::StatementType::For(init, condition, step, body) => {
+                // Variables declared in the header live for the whole loop.
+                let environment = Environment::with_parent(self.environment.clone());
+                let previous = std::mem::replace(&mut self.environment, environment);
+                let result = self.evaluate_for(init, condition, step, body);
+                self.environment = previous;
+
+                return result;
+            }
+            ast::StatementType::ForIn(variable, iterable_expr, body) => {
+                let iterable = self.evaluate_expression(iterable_expr)?;
+                let items: Vec<Value> = match iterable.value_type {
+                    ValueType::List(list) => list.borrow().clone(),
+                    ValueType::Map(map) => map.borrow().keys().map(MapKey::to_value).collect(),
+                    ValueType::String(value) => value
+                        .chars()
+                        .map(|c| Value::new(ValueType::String(c.to_string())))
+                        .collect(),
+                    _ => {
+                        return Err(RuntimeError::NotIterable {
+                            type_name: iterable.type_name(),
+                            span: iterable_expr.span,
+                        })
+                    }
+                };
+
+                for item in items {
+                    let environment = Environment::with_parent(self.environment.clone());
+                    environment.borrow_mut().define(variable.clone(), item);
+
+                    match self.evaluate_in(body, environment)? {
+                        ControlFlow::Break(_) => break,
+                        ControlFlow::Normal | ControlFlow::Continue(_) => {}
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
                     }
                 }
             }
-            _ => {}
+            ast::StatementType::Break => return Ok(ControlFlow::Break(statement.span)),
+            ast::StatementType::Continue => return Ok(ControlFlow::Continue(statement.span)),
+            ast::StatementType::FunctionCall(call) => {
+                self.evaluate_expression(call)?;
+            }
+            ast::StatementType::Error => {
+                return Err(RuntimeError::Unsupported {
+                    what: "a statement that failed to parse".to_string(),
+                    span: statement.span,
+                })
+            }
         }
+
+        Ok(ControlFlow::Normal)
     }
 
-    fn evaluate_expression(&mut self, expr: &Expression) -> Value {
+    fn evaluate_for(
+        &mut self,
+        init: &Option<Box<Statement>>,
+        condition: &Option<Box<Expression>>,
+        step: &Option<Box<Statement>>,
+        body: &[Statement],
+    ) -> Result<ControlFlow, RuntimeError> {
+        if let Some(init) = init {
+            self.evaluate_statement(init)?;
+        }
+
+        loop {
+            if let Some(condition) = condition {
+                if !self.evaluate_expression(condition)?.is_truthy() {
+                    break;
+                }
+            }
+
+            match self.evaluate_scoped(body)? {
+                ControlFlow::Break(_) => break,
+                ControlFlow::Normal | ControlFlow::Continue(_) => {}
+                flow @ ControlFlow::Return(_) => return Ok(flow),
+            }
+
+            if let Some(step) = step {
+                self.evaluate_statement(step)?;
+            }
+        }
+
+        Ok(ControlFlow::Normal)
+    }
+
+    fn evaluate_expression(&mut self, expr: &Expression) -> Result<Value, RuntimeError> {
         match &expr.expression_type {
-            ast::ExpressionType::Literal(ref literal) => match literal.parse::<i64>() {
-                Ok(integer) => Value::new(ValueType::Integer(integer)),
-                Err(_) => Value::new(ValueType::String(literal.clone())),
+            ast::ExpressionType::Literal(ref literal) => match literal {
+                ast::Literal::Int(value) => Ok(Value::new(ValueType::Integer(*value))),
+                #[cfg(feature = "bigint")]
+                ast::Literal::BigInt(value) => Ok(Value::new(ValueType::BigInt(value.clone()))),
+                ast::Literal::Float(value) => Ok(Value::new(ValueType::Float(*value))),
+                ast::Literal::Str(value) => Ok(Value::new(ValueType::String(value.clone()))),
+                ast::Literal::Bool(value) => Ok(Value::new(ValueType::Bool(*value))),
             },
             ast::ExpressionType::Variable(ref variable) => {
-                let variable_stack = self.variable_stack.last().unwrap();
-                let value = variable_stack.get(variable).unwrap();
-                value.clone()
+                match self.environment.borrow().get(variable) {
+                    Some(value) => Ok(value),
+                    None => Err(RuntimeError::UndefinedVariable {
+                        name: variable.clone(),
+                        span: expr.span,
+                    }),
+                }
             }
             ast::ExpressionType::Binary(ref left, ref operator, ref right) => {
-                let left = self.evaluate_expression(left);
-                let right = self.evaluate_expression(right);
+                let left = self.evaluate_expression(left)?;
+
+                // `&&` and `||` only evaluate the right side when they have to.
+                match operator.token_type {
+                    TokenType::And if !left.is_truthy() => {
+                        return Ok(Value::new(ValueType::Bool(false)))
+                    }
+                    TokenType::Or if left.is_truthy() => {
+                        return Ok(Value::new(ValueType::Bool(true)))
+                    }
+                    TokenType::And | TokenType::Or => {
+                        let right = self.evaluate_expression(right)?;
+                        return Ok(Value::new(ValueType::Bool(right.is_truthy())));
+                    }
+                    _ => {}
+                }
+
+                let right = self.evaluate_expression(right)?;
+                let mismatch = RuntimeError::TypeMismatch {
+                    operator: operator.value.clone(),
+                    operands: vec![left.type_name(), right.type_name()],
+                    span: expr.span,
+                };
+
+                if let Some(result) = arithmetic::apply(operator, &left, &right, expr.span) {
+                    return result;
+                }
 
                 match operator.token_type {
                     TokenType::Plus => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Integer(left + right))
-                        }
                         (ValueType::String(left), ValueType::String(right)) => {
-                            Value::new(ValueType::String(left + &right))
-                        }
-                        _ => panic!("hihi"),
-                    },
-                    TokenType::Minus => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Integer(left - right))
+                            Ok(Value::new(ValueType::String(left + &right)))
                         }
-                        _ => panic!("hihi"),
+                        _ => Err(mismatch),
                     },
-                    TokenType::Asterisk => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Integer(left * right))
-                        }
-                        _ => panic!("hihi"),
-                    },
-                    TokenType::Slash => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Integer(left / right))
-                        }
-                        _ => panic!("hihi"),
-                    },
-                    TokenType::Gt => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Bool(left > right))
+                    TokenType::Minus
+                    | TokenType::Asterisk
+                    | TokenType::Slash
+                    | TokenType::Percent
+                    | TokenType::Power => Err(mismatch),
+                    TokenType::Eq | TokenType::NotEq => {
+                        // Numbers compare by value across types, so `1 == 1.0`.
+                        let equal = match arithmetic::compare(&left, &right) {
+                            Some(ordering) => ordering == Some(std::cmp::Ordering::Equal),
+                            None => left == right,
+                        };
+                        Ok(Value::new(ValueType::Bool(
+                            equal == (operator.token_type == TokenType::Eq),
+                        )))
+                    }
+                    TokenType::Gt | TokenType::Lt | TokenType::GtEq | TokenType::LtEq => {
+                        let ordering = match (&left.value_type, &right.value_type) {
+                            (ValueType::String(left), ValueType::String(right)) => {
+                                Some(left.cmp(right))
+                            }
+                            _ => arithmetic::compare(&left, &right).ok_or(mismatch)?,
+                        };
+                        Ok(Value::new(ValueType::Bool(Self::compare(
+                            &operator.token_type,
+                            ordering,
+                        ))))
+                    }
+                    _ => Err(RuntimeError::Unsupported {
+                        what: format!("operator `{}`", operator.value),
+                        span: operator.span,
+                    }),
+                }
+            }
+            ast::ExpressionType::Function(params, body) => {
+                let function = Function {
+                    name: None,
+                    params: params.clone(),
+                    body: body.clone(),
+                    closure: self.environment.clone(),
+                };
+                Ok(Value::new(ValueType::Function(Rc::new(function))))
+            }
+            ast::ExpressionType::Call(callee, args) => {
+                let function = match self.evaluate_expression(callee) {
+                    Err(RuntimeError::UndefinedVariable { name, span })
+                        if matches!(callee.expression_type, ast::ExpressionType::Variable(_)) =>
+                    {
+                        return Err(RuntimeError::UndefinedFunction { name, span })
+                    }
+                    result => result?,
+                };
+
+                match function.value_type {
+                    ValueType::Function(function) => {
+                        self.evaluate_function_call(&function, args, expr.span)
+                    }
+                    ValueType::Builtin(builtin) => {
+                        if let Some(arg) = args.iter().find(|arg| arg.name.is_some()) {
+                            return Err(RuntimeError::InvalidArgument {
+                                message: format!("`{}` doesn't take named arguments", builtin.name),
+                                span: arg.value.span,
+                            });
                         }
-                        _ => panic!("hihi"),
-                    },
-                    TokenType::Lt => match (left.value_type, right.value_type) {
-                        (ValueType::Integer(left), ValueType::Integer(right)) => {
-                            Value::new(ValueType::Bool(left < right))
+                        if builtin.arity != args.len() {
+                            return Err(RuntimeError::ArityMismatch {
+                                name: builtin.name.to_string(),
+                                expected: builtin.arity,
+                                found: args.len(),
+                                span: expr.span,
+                            });
                         }
-                        _ => panic!("hihi"),
-                    },
-                    _ => panic!("Cannot evaluate expression"),
+
+                        let args = args
+                            .iter()
+                            .map(|arg| self.evaluate_expression(&arg.value))
+                            .collect::<Result<Vec<Value>, RuntimeError>>()?;
+                        (builtin.function)(&args, expr.span)
+                    }
+                    _ => Err(RuntimeError::NotCallable {
+                        type_name: function.type_name(),
+                        span: callee.span,
+                    }),
+                }
+            }
+            ast::ExpressionType::List(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.evaluate_expression(element))
+                    .collect::<Result<Vec<Value>, RuntimeError>>()?;
+                Ok(Value::new(ValueType::List(Rc::new(RefCell::new(elements)))))
+            }
+            ast::ExpressionType::Map(entries) => {
+                let mut map = BTreeMap::new();
+                for (key, value) in entries {
+                    let key_value = self.evaluate_expression(key)?;
+                    let key_value = Self::map_key(&key_value, key.span)?;
+                    map.insert(key_value, self.evaluate_expression(value)?);
                 }
+                Ok(Value::new(ValueType::Map(Rc::new(RefCell::new(map)))))
             }
-            ast::ExpressionType::FunctionCall(name, args) => {
-                let function = self.functions.get(name).unwrap();
-                let params = match function.statement_type {
-                    ast::StatementType::FunctionDeclaration(_, ref params, _) => params.clone(),
-                    _ => panic!("Cannot evaluate expression"),
+            ast::ExpressionType::Struct(name, fields) => {
+                let struct_type = match self.environment.borrow().get(name) {
+                    Some(Value {
+                        value_type: ValueType::StructType(struct_type),
+                    }) => struct_type,
+                    _ => {
+                        return Err(RuntimeError::UnknownStruct {
+                            name: name.clone(),
+                            span: expr.span,
+                        })
+                    }
                 };
 
-                self.evaluate_function_call(name.clone(), params, *args.clone())
+                // Initializers run in the order they are written, and are
+                // then put in the order the fields are declared.
+                let mut given = Vec::with_capacity(fields.len());
+                for (field, value) in fields {
+                    given.push((field, self.evaluate_expression(value)?));
+                }
+
+                let mut slots: Vec<Option<Value>> = vec![None; struct_type.fields.len()];
+                for (field, value) in given {
+                    let Some(position) = struct_type.fields.iter().position(|f| f == field) else {
+                        return Err(RuntimeError::UnknownField {
+                            type_name: format!("`{}`", name),
+                            field: field.clone(),
+                            span: expr.span,
+                        });
+                    };
+                    if slots[position].replace(value).is_some() {
+                        return Err(RuntimeError::DuplicateField {
+                            struct_name: name.clone(),
+                            field: field.clone(),
+                            span: expr.span,
+                        });
+                    }
+                }
+
+                let mut values = Vec::with_capacity(slots.len());
+                for (declared, value) in struct_type.fields.iter().zip(slots) {
+                    let Some(value) = value else {
+                        return Err(RuntimeError::MissingField {
+                            struct_name: name.clone(),
+                            field: declared.clone(),
+                            span: expr.span,
+                        });
+                    };
+                    values.push(value);
+                }
+
+                let record = Record {
+                    struct_type,
+                    values,
+                };
+                Ok(Value::new(ValueType::Struct(Rc::new(RefCell::new(record)))))
+            }
+            ast::ExpressionType::Field(record, field) => {
+                let target = self.evaluate_expression(record)?;
+                let record = Self::record(&target, field, expr.span)?.borrow();
+                let position = Self::field_position(&record, field, expr.span)?;
+                Ok(record.values[position].clone())
+            }
+            ast::ExpressionType::Index(collection, index) => {
+                let collection = self.evaluate_expression(collection)?;
+                let position = self.evaluate_expression(index)?;
+
+                match &collection.value_type {
+                    ValueType::List(list) => {
+                        let list = list.borrow();
+                        let position = Self::list_position(&list, &position, index.span)?;
+                        Ok(list[position].clone())
+                    }
+                    ValueType::String(value) => {
+                        let chars: Vec<char> = value.chars().collect();
+                        let position = Self::list_position(&chars, &position, index.span)?;
+                        Ok(Value::new(ValueType::String(chars[position].to_string())))
+                    }
+                    ValueType::Map(map) => {
+                        let key = Self::map_key(&position, index.span)?;
+                        match map.borrow().get(&key) {
+                            Some(value) => Ok(value.clone()),
+                            None => Err(RuntimeError::KeyNotFound {
+                                key: key.to_string(),
+                                span: index.span,
+                            }),
+                        }
+                    }
+                    _ => Err(RuntimeError::TypeMismatch {
+                        operator: "[]".to_string(),
+                        operands: vec![collection.type_name(), position.type_name()],
+                        span: expr.span,
+                    }),
+                }
+            }
+            ast::ExpressionType::Unary(ref operator, ref operand) => match operator.token_type {
+                TokenType::Bang => {
+                    let operand = self.evaluate_expression(operand)?;
+                    match operand.value_type {
+                        ValueType::Bool(value) => Ok(Value::new(ValueType::Bool(!value))),
+                        _ => Err(RuntimeError::TypeMismatch {
+                            operator: operator.value.clone(),
+                            operands: vec![operand.type_name()],
+                            span: expr.span,
+                        }),
+                    }
+                }
+                _ => {
+                    let operand = self.evaluate_expression(operand)?;
+                    arithmetic::apply_unary(operator, &operand, expr.span).unwrap_or_else(|| {
+                        Err(RuntimeError::TypeMismatch {
+                            operator: operator.value.clone(),
+                            operands: vec![operand.type_name()],
+                            span: expr.span,
+                        })
+                    })
+                }
+            },
+            ast::ExpressionType::Error => Err(RuntimeError::Unsupported {
+                what: "an expression that failed to parse".to_string(),
+                span: expr.span,
+            }),
+        }
+    }
+
+    /// Applies a comparison operator to an ordering. Unordered values (NaN)
+    /// compare false.
+    fn compare(operator: &TokenType, ordering: Option<std::cmp::Ordering>) -> bool {
+        ordering.is_some_and(|ordering| match operator {
+            TokenType::Gt => ordering.is_gt(),
+            TokenType::Lt => ordering.is_lt(),
+            TokenType::GtEq => ordering.is_ge(),
+            _ => ordering.is_le(),
+        })
+    }
+
+    /// The struct instance behind `value`, for accessing `field` on it.
+    fn record<'a>(
+        value: &'a Value,
+        field: &str,
+        span: Span,
+    ) -> Result<&'a Rc<RefCell<Record>>, RuntimeError> {
+        match &value.value_type {
+            ValueType::Struct(record) => Ok(record),
+            _ => Err(RuntimeError::UnknownField {
+                type_name: value.type_name().to_string(),
+                field: field.to_string(),
+                span,
+            }),
+        }
+    }
+
+    fn field_position(record: &Record, field: &str, span: Span) -> Result<usize, RuntimeError> {
+        record
+            .position(field)
+            .ok_or_else(|| RuntimeError::UnknownField {
+                type_name: format!("`{}`", record.struct_type.name),
+                field: field.to_string(),
+                span,
+            })
+    }
+
+    /// Checks that `index` is an integer within the bounds of `items`.
+    fn list_position<T>(items: &[T], index: &Value, span: Span) -> Result<usize, RuntimeError> {
+        let index = match index.value_type {
+            ValueType::Integer(index) => index,
+            #[cfg(feature = "bigint")]
+            ValueType::BigInt(_) => {
+                return Err(RuntimeError::IntegerTooLarge {
+                    purpose: "an index",
+                    span,
+                })
+            }
+            _ => {
+                return Err(RuntimeError::TypeMismatch {
+                    operator: "[]".to_string(),
+                    operands: vec!["List", index.type_name()],
+                    span,
+                })
             }
-            _ => panic!("Cannot evaluate expression"),
+        };
+
+        if index < 0 || index as usize >= items.len() {
+            return Err(RuntimeError::IndexOutOfBounds {
+                index,
+                length: items.len(),
+                span,
+            });
         }
+
+        Ok(index as usize)
     }
 
+    fn map_key(key: &Value, span: Span) -> Result<MapKey, RuntimeError> {
+        #[cfg(feature = "bigint")]
+        if let ValueType::BigInt(_) = key.value_type {
+            return Err(RuntimeError::IntegerTooLarge {
+                purpose: "a map key",
+                span,
+            });
+        }
+        MapKey::from_value(key).ok_or_else(|| RuntimeError::TypeMismatch {
+            operator: "[]".to_string(),
+            operands: vec!["Map", key.type_name()],
+            span,
+        })
+    }
+
+    /// Binds positional arguments in order, then named ones, then defaults for
+    /// whatever is left. Extra positional arguments go to the rest parameter.
     fn evaluate_function_call(
         &mut self,
-        name: String,
-        params: Vec<String>,
-        args: Vec<Expression>,
-    ) -> Value {
-        let body = match self.functions.get(&name).unwrap().statement_type {
-            ast::StatementType::FunctionDeclaration(_, _, ref body) => body.clone(),
-            _ => panic!("Cannot evaluate expression"),
+        function: &Function,
+        args: &[ast::Argument],
+        span: Span,
+    ) -> Result<Value, RuntimeError> {
+        let mut positional = Vec::new();
+        let mut named = Vec::new();
+        for arg in args {
+            let value = self.evaluate_expression(&arg.value)?;
+            match &arg.name {
+                Some(name) => named.push((name, value, arg.value.span)),
+                None => positional.push(value),
+            }
+        }
+
+        let (params, rest) = match function.params.split_last() {
+            Some((last, params)) if last.rest => (params, Some(last)),
+            _ => (&function.params[..], None),
         };
-        let mut variable_stack = HashMap::new();
-        for (param, arg) in params.iter().zip(args.iter()) {
-            let value = self.evaluate_expression(arg);
-            variable_stack.insert(param.clone(), value);
+
+        let found = positional.len();
+        let mut positional = positional.into_iter();
+        let mut bound: Vec<Option<Value>> = params.iter().map(|_| positional.next()).collect();
+        let extra: Vec<Value> = positional.collect();
+        if !extra.is_empty() && rest.is_none() {
+            return Err(RuntimeError::ArityMismatch {
+                name: function.display_name().to_string(),
+                expected: params.len(),
+                found,
+                span,
+            });
         }
-        self.variable_stack.push(variable_stack);
 
-        let mut return_value = Value::new(ValueType::Integer(0));
-        for statement in body.into_iter() {
-            match statement.statement_type {
-                ast::StatementType::Return(ref expr) => match expr {
-                    Some(expr) => {
-                        return_value = self.evaluate_expression(expr);
-                    }
-                    None => {
-                        return_value = Value::new(ValueType::Integer(0));
+        for (name, value, arg_span) in named {
+            let Some(position) = params.iter().position(|param| &param.name == name) else {
+                return Err(RuntimeError::UnknownArgument {
+                    function: function.display_name().to_string(),
+                    name: name.clone(),
+                    span: arg_span,
+                });
+            };
+            if bound[position].is_some() {
+                return Err(RuntimeError::DuplicateArgument {
+                    function: function.display_name().to_string(),
+                    name: name.clone(),
+                    span: arg_span,
+                });
+            }
+            bound[position] = Some(value);
+        }
+
+        let environment = Environment::with_parent(function.closure.clone());
+        for (param, value) in params.iter().zip(bound) {
+            let value = match (value, &param.default) {
+                (Some(value), _) => value,
+                // Defaults are evaluated on every call, inside the new scope so
+                // they can refer to earlier parameters.
+                (None, Some(default)) => {
+                    let previous = std::mem::replace(&mut self.environment, environment.clone());
+                    let value = self.evaluate_expression(default);
+                    self.environment = previous;
+                    value?
+                }
+                (None, None) => {
+                    return Err(RuntimeError::MissingArgument {
+                        function: function.display_name().to_string(),
+                        name: param.name.clone(),
+                        span,
+                    })
+                }
+            };
+            environment.borrow_mut().define(param.name.clone(), value);
+        }
+        if let Some(rest) = rest {
+            environment.borrow_mut().define(
+                rest.name.clone(),
+                Value::new(ValueType::List(Rc::new(RefCell::new(extra)))),
+            );
+        }
+
+        let result = self.evaluate_in(&function.body, environment)?.finish()?;
+
+        Ok(result.unwrap_or_else(|| Value::new(ValueType::Integer(0))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::Lexer;
+    use crate::compiler::parser::Parser;
+
+    /// Runs `source` without the checker, so the evaluator's own errors show.
+    fn run(source: &str) -> Result<Rc<RefCell<Environment>>, RuntimeError> {
+        let tokens = Lexer::new(source).lex().expect("source should lex");
+        let program = Parser::new(tokens).parse().expect("source should parse");
+        let mut evaluator = Evaluator::new(program);
+        evaluator.evaluate()?;
+
+        Ok(evaluator.globals)
+    }
+
+    /// Runs `source` and shows the global `name` the way `print` would.
+    fn global(source: &str, name: &str) -> String {
+        let globals = run(source).expect("source should run");
+        let value = globals.borrow().get(name).expect("global should exist");
+        value.to_string()
+    }
+
+    fn error(source: &str) -> RuntimeError {
+        run(source).expect_err("source should fail")
+    }
+
+    #[test]
+    fn rejects_break_and_continue_outside_loops() {
+        for (source, message, column) in [
+            ("let a = 1; break;", "`break` outside of a loop", 12),
+            (
+                "func f() { continue; } f();",
+                "`continue` outside of a loop",
+                12,
+            ),
+            (
+                "func f() { break; } while (true) { f(); }",
+                "`break` outside of a loop",
+                12,
+            ),
+        ] {
+            let error = error(source);
+            assert_eq!(
+                (error.to_string(), error.span().column),
+                (message.to_string(), column),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn returns_from_inside_nested_blocks() {
+        let source = "
+            func find(items, wanted) {
+                let i = 0;
+                while (true) {
+                    if (items[i] == wanted) {
+                        for (let j = 0; j < 1; j = j + 1) {
+                            return i;
+                        }
                     }
-                },
-                _ => self.evaluate_statement(&statement),
+                    i = i + 1;
+                }
+            }
+            let found = find([4, 5, 6], 6);
+        ";
+
+        assert_eq!(global(source, "found"), "2");
+    }
+
+    #[test]
+    fn stops_the_program_at_a_top_level_return() {
+        assert_eq!(global("let a = 1; return; a = 2;", "a"), "1");
+    }
+
+    #[test]
+    fn undoes_shadowing_when_a_block_ends() {
+        let source = "
+            let a = 1;
+            let inner = 0;
+            if (true) {
+                let a = 2;
+                inner = a;
+            }
+        ";
+
+        assert_eq!(global(source, "a"), "1");
+        assert_eq!(global(source, "inner"), "2");
+    }
+
+    #[test]
+    fn assigns_through_enclosing_scopes() {
+        let source = "
+            let total = 0;
+            func add(n) { total = total + n; }
+            while (total < 3) { add(1); }
+        ";
+
+        assert_eq!(global(source, "total"), "3");
+    }
+
+    #[test]
+    fn rejects_assignment_to_undeclared_names() {
+        let error = error("if (true) { let a = 1; } a = 2;");
+
+        assert_eq!(error.to_string(), "Undefined variable `a`");
+        assert_eq!(error.span().column, 26);
+    }
+
+    #[test]
+    fn captures_each_loop_iteration_separately() {
+        let source = "
+            let callbacks = [];
+            for item in [1, 2, 3] {
+                push(callbacks, fn() { return item * 10; });
+            }
+            let results = [];
+            for callback in callbacks {
+                push(results, callback());
             }
+        ";
+
+        assert_eq!(global(source, "results"), "[10, 20, 30]");
+    }
+
+    #[test]
+    fn passes_and_returns_functions() {
+        let source = "
+            func counter() {
+                let count = 0;
+                return fn() { count = count + 1; return count; };
+            }
+            func twice(f) { f(); return f(); }
+            let next = counter();
+            let second = twice(next);
+            let third = next();
+        ";
+
+        assert_eq!(global(source, "second"), "2");
+        assert_eq!(global(source, "third"), "3");
+    }
+
+    #[test]
+    fn binds_positional_named_default_and_rest_arguments() {
+        let source = "
+            func f(a, b = a + 1, ...rest) { return [a, b, rest]; }
+            let positional = f(1, 2);
+            let defaulted = f(1);
+            let named = f(b: 3, a: 1);
+            let extra = f(1, 2, 3, 4);
+        ";
+
+        assert_eq!(global(source, "positional"), "[1, 2, []]");
+        assert_eq!(global(source, "defaulted"), "[1, 2, []]");
+        assert_eq!(global(source, "named"), "[1, 3, []]");
+        assert_eq!(global(source, "extra"), "[1, 2, [3, 4]]");
+    }
+
+    #[test]
+    fn reports_bad_arguments() {
+        let function = "func f(a, b = 2) { return a; }";
+        for (call, message) in [
+            (
+                "f(1, a: 2);",
+                "Argument `a` is given more than once in call to `f`",
+            ),
+            ("f(1, c: 2);", "Function `f` has no parameter `c`"),
+            ("f(b: 1);", "Missing argument `a` in call to `f`"),
+            (
+                "f(1, 2, 3);",
+                "Function `f` takes 2 argument(s) but 3 were given",
+            ),
+        ] {
+            let source = format!("{} {}", function, call);
+            assert_eq!(error(&source).to_string(), message, "{}", call);
         }
+    }
+
+    #[test]
+    fn evaluates_struct_fields_in_source_order() {
+        let source = "
+            struct Point { x, y }
+            let calls = [];
+            func note(value) { push(calls, value); return value; }
+            let p = Point { y: note(2), x: note(1) };
+        ";
+
+        assert_eq!(global(source, "calls"), "[2, 1]");
+        assert_eq!(global(source, "p"), "Point { x: 1, y: 2 }");
+    }
+
+    #[test]
+    fn reports_bad_struct_fields() {
+        let declaration = "struct Point { x, y }";
+        for (literal, message) in [
+            (
+                "Point { x: 1, x: 2, y: 3 }",
+                "Duplicate field `x` in struct `Point`",
+            ),
+            ("Point { x: 1 }", "Missing field `y` in `Point`"),
+            ("Point { x: 1, y: 2, z: 3 }", "No field `z` on `Point`"),
+        ] {
+            let source = format!("{} let p = {};", declaration, literal);
+            assert_eq!(error(&source).to_string(), message, "{}", literal);
+        }
+    }
 
-        self.variable_stack.pop();
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn evaluates_big_integer_literals() {
+        let source = "let big = 99999999999999999999 + 1;";
 
-        return_value
+        assert_eq!(global(source, "big"), "100000000000000000000");
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn rejects_big_integers_as_indexes_and_keys() {
+        for (source, message) in [
+            (
+                "let a = [1][99999999999999999999];",
+                "Integer is too large to use as an index",
+            ),
+            (
+                "let a = {1: 2}[-99999999999999999999];",
+                "Integer is too large to use as a map key",
+            ),
+        ] {
+            assert_eq!(error(source).to_string(), message, "{}", source);
+        }
     }
 }
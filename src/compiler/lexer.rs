@@ -1,10 +1,78 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use super::span::Span;
 #[allow(dead_code)]
 pub use super::token::{Token, TokenType};
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug, Clone)]
+pub enum LexError {
+    UnterminatedString { span: Span },
+    UnterminatedComment { span: Span },
+    InvalidEscape { sequence: String, span: Span },
+    UnexpectedCharacter { character: char, span: Span },
+    MalformedNumber { text: String, span: Span },
+}
+
+impl LexError {
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnterminatedString { span }
+            | LexError::UnterminatedComment { span }
+            | LexError::InvalidEscape { span, .. }
+            | LexError::UnexpectedCharacter { span, .. }
+            | LexError::MalformedNumber { span, .. } => *span,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::new(self.to_string(), self.span());
+        match self {
+            LexError::UnterminatedString { .. } => {
+                diagnostic.with_help("add a closing `\"` to the string")
+            }
+            LexError::UnterminatedComment { .. } => {
+                diagnostic.with_help("every `/*` needs a matching `*/`")
+            }
+            LexError::InvalidEscape { .. } => diagnostic.with_help(
+                "valid escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"` and `\\u{...}`",
+            ),
+            LexError::MalformedNumber { .. } => {
+                diagnostic.with_help("identifiers can't start with a digit")
+            }
+            LexError::UnexpectedCharacter { .. } => diagnostic,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnterminatedString { .. } => write!(f, "Unterminated string literal"),
+            LexError::UnterminatedComment { .. } => write!(f, "Unterminated block comment"),
+            LexError::InvalidEscape { sequence, .. } => {
+                write!(f, "Invalid escape sequence `{}`", sequence)
+            }
+            LexError::UnexpectedCharacter { character, .. } => {
+                write!(f, "Unexpected character `{}`", character)
+            }
+            LexError::MalformedNumber { text, .. } => write!(f, "Malformed number `{}`", text),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
 
 #[derive(Debug)]
 pub struct Lexer<'a> {
     source: &'a str,
-    position: usize,
+    /// Byte offset of `source` within the text spans refer to.
+    base: usize,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    column: usize,
     tmp_buffer: String,
 }
 
@@ -12,10 +80,24 @@ impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
             source: input,
-            position: 0,
+            base: 0,
+            chars: input.char_indices().peekable(),
+            line: 1,
+            column: 1,
             tmp_buffer: String::new(),
         }
     }
+
+    /// Lexes `input` as a continuation of a longer text, starting at byte
+    /// `offset` on line `line` of it, so spans point into the longer text.
+    /// `offset` must be the start of a line.
+    pub fn continuing(input: &'a str, offset: usize, line: usize) -> Self {
+        Self {
+            base: offset,
+            line,
+            ..Self::new(input)
+        }
+    }
 }
 
 impl<'a> Lexer<'a> {
@@ -24,100 +106,631 @@ impl<'a> Lexer<'a> {
             "let" => TokenType::Let,
             "if" => TokenType::If,
             "else" => TokenType::Else,
-            "func" => TokenType::Func,
+            "while" => TokenType::While,
+            "for" => TokenType::For,
+            "in" => TokenType::In,
+            "break" => TokenType::Break,
+            "continue" => TokenType::Continue,
+            "func" | "fn" => TokenType::Func,
+            "struct" => TokenType::Struct,
             "return" => TokenType::Return,
             "print" => TokenType::Print,
+            "true" => TokenType::True,
+            "false" => TokenType::False,
             _ => TokenType::Identif,
         }
     }
 
-    fn parse_number(&mut self) -> Token {
-        let mut current_char = self.source.chars().nth(self.position).unwrap();
+    fn current_char(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// The character after the current one.
+    fn next_char(&self) -> Option<char> {
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars.next().map(|(_, c)| c)
+    }
+
+    /// Byte offset of the current character, or the source length at the end.
+    fn offset(&mut self) -> usize {
+        let offset = match self.chars.peek() {
+            Some(&(offset, _)) => offset,
+            None => self.source.len(),
+        };
+
+        self.base + offset
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let (_, current_char) = self.chars.next()?;
+
+        if current_char == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        Some(current_char)
+    }
 
-        while self.position < self.source.len() && current_char.is_numeric() {
+    /// An empty span at the current character, to be closed by `finish_span`.
+    fn start_span(&mut self) -> Span {
+        let offset = self.offset();
+        Span::new(offset, offset, self.line, self.column)
+    }
+
+    fn finish_span(&mut self, start: Span) -> Span {
+        Span {
+            end: self.offset(),
+            ..start
+        }
+    }
+
+    /// Takes the contents of `tmp_buffer` as a token starting at `start`.
+    fn take_token(&mut self, token_type: TokenType, start: Span) -> Token {
+        let token = Token::new(token_type, self.tmp_buffer.clone(), self.finish_span(start));
+        self.tmp_buffer.clear();
+
+        token
+    }
+
+    fn take_digits(&mut self) {
+        while let Some(current_char) = self.current_char() {
+            if !current_char.is_ascii_digit() {
+                break;
+            }
             self.tmp_buffer.push(current_char);
-            self.position += 1;
-            current_char = self.source.chars().nth(self.position).unwrap();
+            self.advance();
+        }
+    }
+
+    /// Lexes `42`, `3.14` and `1e-9`. The parser tells integers from floats by
+    /// looking for a `.` or an exponent in the token text.
+    fn parse_number(&mut self) -> Result<Token, LexError> {
+        let start = self.start_span();
+
+        self.take_digits();
+
+        // A `.` only belongs to the number when a digit follows it.
+        if self.current_char() == Some('.') && self.next_char().is_some_and(|c| c.is_ascii_digit())
+        {
+            self.tmp_buffer.push('.');
+            self.advance();
+            self.take_digits();
+        }
+
+        // Likewise an exponent needs digits, so `2e` stays a malformed number.
+        if let Some(exponent @ ('e' | 'E')) = self.current_char() {
+            let mut lookahead = self.chars.clone();
+            lookahead.next();
+            let sign = lookahead.next_if(|&(_, c)| c == '+' || c == '-');
+            if lookahead.peek().is_some_and(|&(_, c)| c.is_ascii_digit()) {
+                self.tmp_buffer.push(exponent);
+                self.advance();
+                if let Some((_, sign)) = sign {
+                    self.tmp_buffer.push(sign);
+                    self.advance();
+                }
+                self.take_digits();
+            }
+        }
 
-            if !current_char.is_alphanumeric() {
-                self.position -= 1;
+        // `12ab` is neither a number nor an identifier.
+        if self
+            .current_char()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+        {
+            while let Some(current_char) = self.current_char() {
+                if !current_char.is_alphanumeric() && current_char != '_' {
+                    break;
+                }
+                self.tmp_buffer.push(current_char);
+                self.advance();
             }
+
+            let token = self.take_token(TokenType::Number, start);
+            return Err(LexError::MalformedNumber {
+                text: token.value,
+                span: token.span,
+            });
         }
-        let token = Token::new(TokenType::Number, self.tmp_buffer.clone());
-        self.tmp_buffer.clear();
 
-        return token;
+        Ok(self.take_token(TokenType::Number, start))
     }
 
-    fn parse_string(&mut self) -> Token {
-        self.position += 1;
+    fn parse_string(&mut self) -> Result<Token, LexError> {
+        let start = self.start_span();
+        self.advance();
 
-        let mut current_char = self.source.chars().nth(self.position).unwrap();
+        loop {
+            match self.current_char() {
+                Some('\"') => {
+                    self.advance();
+                    return Ok(self.take_token(TokenType::String, start));
+                }
+                Some('\\') => match self.parse_escape() {
+                    Ok(escaped) => self.tmp_buffer.push(escaped),
+                    Err(LexError::UnterminatedString { .. }) => {
+                        self.tmp_buffer.clear();
+                        return Err(LexError::UnterminatedString {
+                            span: self.finish_span(start),
+                        });
+                    }
+                    Err(error) => {
+                        self.tmp_buffer.clear();
+                        return Err(error);
+                    }
+                },
+                Some(current_char) => {
+                    self.tmp_buffer.push(current_char);
+                    self.advance();
+                }
+                None => {
+                    self.tmp_buffer.clear();
+                    return Err(LexError::UnterminatedString {
+                        span: self.finish_span(start),
+                    });
+                }
+            }
+        }
+    }
 
-        while self.position < self.source.len() && current_char != '\"' {
-            self.tmp_buffer.push(current_char);
-            self.position += 1;
-            current_char = self.source.chars().nth(self.position).unwrap();
+    /// Parses an escape sequence starting at the current `\`.
+    fn parse_escape(&mut self) -> Result<char, LexError> {
+        let start = self.start_span();
+        self.advance();
+
+        let escaped = match self.advance() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('\"') => '\"',
+            Some('u') => return self.parse_unicode_escape(start),
+            Some(other) => {
+                return Err(LexError::InvalidEscape {
+                    sequence: format!("\\{}", other),
+                    span: self.finish_span(start),
+                })
+            }
+            None => {
+                return Err(LexError::UnterminatedString {
+                    span: self.finish_span(start),
+                })
+            }
+        };
+
+        Ok(escaped)
+    }
+
+    /// Parses the `{XXXX}` part of a `\u{XXXX}` escape.
+    fn parse_unicode_escape(&mut self, start: Span) -> Result<char, LexError> {
+        let mut sequence = String::from("\\u");
+        let mut digits = String::new();
+
+        if self.current_char() == Some('{') {
+            sequence.push('{');
+            self.advance();
+
+            while let Some(current_char) = self.current_char() {
+                if current_char == '}' || current_char == '\"' {
+                    break;
+                }
+                sequence.push(current_char);
+                digits.push(current_char);
+                self.advance();
+            }
+
+            if self.current_char() == Some('}') {
+                sequence.push('}');
+                self.advance();
+
+                // At most six hex digits, like Rust.
+                let escaped = u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .filter(|_| digits.len() <= 6)
+                    .and_then(char::from_u32);
+                if let Some(escaped) = escaped {
+                    return Ok(escaped);
+                }
+            }
         }
 
-        let token = Token::new(TokenType::String, self.tmp_buffer.clone());
-        self.tmp_buffer.clear();
-        return token;
+        Err(LexError::InvalidEscape {
+            sequence,
+            span: self.finish_span(start),
+        })
     }
 
-    fn parse_identif(&mut self) -> Token {
-        let mut current_char = self.source.chars().nth(self.position).unwrap();
+    /// Parses `r"..."`, in which backslashes have no special meaning.
+    fn parse_raw_string(&mut self) -> Result<Token, LexError> {
+        let start = self.start_span();
+        self.advance();
+        self.advance();
 
-        while self.position < self.source.len() && current_char.is_alphanumeric() {
-            self.tmp_buffer.push(current_char);
-            self.position += 1;
-            current_char = self.source.chars().nth(self.position).unwrap();
+        loop {
+            match self.advance() {
+                Some('\"') => return Ok(self.take_token(TokenType::String, start)),
+                Some(current_char) => self.tmp_buffer.push(current_char),
+                None => {
+                    self.tmp_buffer.clear();
+                    return Err(LexError::UnterminatedString {
+                        span: self.finish_span(start),
+                    });
+                }
+            }
+        }
+    }
 
-            if !current_char.is_alphanumeric() {
-                self.position -= 1;
+    /// Lexes `...`. Two dots on their own aren't a token.
+    fn parse_ellipsis(&mut self) -> Result<Token, LexError> {
+        let start = self.start_span();
+
+        for _ in 0..3 {
+            if self.current_char() != Some('.') {
+                return Err(LexError::UnexpectedCharacter {
+                    character: '.',
+                    span: self.finish_span(start),
+                });
             }
+            self.tmp_buffer.push('.');
+            self.advance();
         }
 
-        let token_type = self.parse_keyword();
+        Ok(self.take_token(TokenType::Ellipsis, start))
+    }
 
-        let token = Token::new(token_type, self.tmp_buffer.clone());
-        self.tmp_buffer.clear();
-        return token;
+    /// Skips a `//` comment up to the end of the line. `///` doc comments are
+    /// kept as `DocComment` tokens holding the text after the slashes.
+    fn parse_line_comment(&mut self) -> Option<Token> {
+        let start = self.start_span();
+        self.advance();
+        self.advance();
+
+        // `////...` is a plain comment, like in Rust.
+        let is_doc = self.current_char() == Some('/') && self.next_char() != Some('/');
+        if is_doc {
+            self.advance();
+        }
+
+        while let Some(current_char) = self.current_char() {
+            if current_char == '\n' {
+                break;
+            }
+            if is_doc {
+                self.tmp_buffer.push(current_char);
+            }
+            self.advance();
+        }
+
+        if is_doc {
+            Some(self.take_token(TokenType::DocComment, start))
+        } else {
+            None
+        }
     }
 
-    pub fn lex(&mut self) -> Vec<Token> {
-        let mut current_char: char;
+    /// Skips a `/* ... */` comment, which may contain nested block comments.
+    fn skip_block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.start_span();
+        self.advance();
+        self.advance();
+        let mut depth = 1;
+
+        while depth > 0 {
+            match (self.current_char(), self.next_char()) {
+                (Some('/'), Some('*')) => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                }
+                (Some('*'), Some('/')) => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                }
+                (Some(_), _) => {
+                    self.advance();
+                }
+                (None, _) => {
+                    return Err(LexError::UnterminatedComment {
+                        span: self.finish_span(start),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_identif(&mut self) -> Token {
+        let start = self.start_span();
+
+        while let Some(current_char) = self.current_char() {
+            if !current_char.is_alphanumeric() && current_char != '_' {
+                break;
+            }
+            self.tmp_buffer.push(current_char);
+            self.advance();
+        }
+
+        let token_type = self.parse_keyword();
+        self.take_token(token_type, start)
+    }
+
+    pub fn lex(&mut self) -> Result<Vec<Token>, LexError> {
         let mut tokens: Vec<Token> = Vec::new();
 
-        while self.position < self.source.len() {
-            current_char = self.source.chars().nth(self.position).unwrap();
-
-            let token: Token = match current_char {
-                '\"' => self.parse_string(),
-                '0'..='9' => self.parse_number(),
-                'a'..='z' | 'A'..='Z' => self.parse_identif(),
-                ')' => Token::new(TokenType::RParen, current_char.to_string()),
-                '(' => Token::new(TokenType::LParen, current_char.to_string()),
-                '{' => Token::new(TokenType::LBrace, current_char.to_string()),
-                '}' => Token::new(TokenType::RBrace, current_char.to_string()),
-                '=' => Token::new(TokenType::Assign, current_char.to_string()),
-                ';' => Token::new(TokenType::Semicolon, current_char.to_string()),
-                '+' => Token::new(TokenType::Plus, current_char.to_string()),
-                '-' => Token::new(TokenType::Minus, current_char.to_string()),
-                '*' => Token::new(TokenType::Asterisk, current_char.to_string()),
-                '/' => Token::new(TokenType::Slash, current_char.to_string()),
-                '>' => Token::new(TokenType::Gt, current_char.to_string()),
-                '<' => Token::new(TokenType::Lt, current_char.to_string()),
-                ',' => Token::new(TokenType::Comma, current_char.to_string()),
-                _ => {
-                    self.position += 1;
+        while let Some(current_char) = self.current_char() {
+            // Literals and identifiers consume their own characters, everything
+            // else below is a single character.
+            match current_char {
+                '\"' => {
+                    tokens.push(self.parse_string()?);
+                    continue;
+                }
+                '0'..='9' => {
+                    tokens.push(self.parse_number()?);
+                    continue;
+                }
+                'r' if self.next_char() == Some('\"') => {
+                    tokens.push(self.parse_raw_string()?);
+                    continue;
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    tokens.push(self.parse_identif());
+                    continue;
+                }
+                '.' if self.next_char() == Some('.') => {
+                    tokens.push(self.parse_ellipsis()?);
                     continue;
                 }
+                '/' if self.next_char() == Some('/') => {
+                    tokens.extend(self.parse_line_comment());
+                    continue;
+                }
+                '/' if self.next_char() == Some('*') => {
+                    self.skip_block_comment()?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            let start = self.start_span();
+            self.advance();
+
+            // Operators that may be two characters long.
+            let two_char_type = match (current_char, self.current_char()) {
+                ('=', Some('=')) => Some(TokenType::Eq),
+                ('!', Some('=')) => Some(TokenType::NotEq),
+                ('>', Some('=')) => Some(TokenType::GtEq),
+                ('<', Some('=')) => Some(TokenType::LtEq),
+                ('&', Some('&')) => Some(TokenType::And),
+                ('|', Some('|')) => Some(TokenType::Or),
+                ('*', Some('*')) => Some(TokenType::Power),
+                _ => None,
             };
-            tokens.push(token);
+            if let Some(token_type) = two_char_type {
+                self.advance();
+                let span = self.finish_span(start);
+                let value = self.source[span.start - self.base..span.end - self.base].to_string();
+                tokens.push(Token::new(token_type, value, span));
+                continue;
+            }
+
+            let span = self.finish_span(start);
+            let token_type = match current_char {
+                ')' => TokenType::RParen,
+                '(' => TokenType::LParen,
+                '{' => TokenType::LBrace,
+                '}' => TokenType::RBrace,
+                ']' => TokenType::RBracket,
+                '[' => TokenType::LBracket,
+                '=' => TokenType::Assign,
+                ';' => TokenType::Semicolon,
+                ':' => TokenType::Colon,
+                '.' => TokenType::Dot,
+                '+' => TokenType::Plus,
+                '-' => TokenType::Minus,
+                '*' => TokenType::Asterisk,
+                '/' => TokenType::Slash,
+                '%' => TokenType::Percent,
+                '>' => TokenType::Gt,
+                '<' => TokenType::Lt,
+                '!' => TokenType::Bang,
+                ',' => TokenType::Comma,
+                c if c.is_whitespace() => continue,
+                c => return Err(LexError::UnexpectedCharacter { character: c, span }),
+            };
+            tokens.push(Token::new(token_type, current_char.to_string(), span));
+        }
 
-            self.position += 1;
+        let end = self.start_span();
+        tokens.push(Token::new(TokenType::Eof, String::new(), end));
+
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<Token> {
+        Lexer::new(source).lex().expect("source should lex")
+    }
+
+    fn error(source: &str) -> LexError {
+        Lexer::new(source).lex().expect_err("source should not lex")
+    }
+
+    #[test]
+    fn reports_unterminated_strings() {
+        let error = error("let s = \"open\nprint s;");
+
+        assert!(matches!(error, LexError::UnterminatedString { .. }));
+        assert_eq!((error.span().line, error.span().column), (1, 9));
+        assert_eq!(error.span().end, 22);
+    }
+
+    #[test]
+    fn reports_stray_characters() {
+        for (source, character, column) in [("let a = @;", '@', 9), ("print 1 # 2;", '#', 9)] {
+            match error(source) {
+                LexError::UnexpectedCharacter {
+                    character: found,
+                    span,
+                } => assert_eq!((found, span.column), (character, column)),
+                other => panic!("{}: {:?}", source, other),
+            }
         }
-        return tokens;
+    }
+
+    #[test]
+    fn lexes_numbers_followed_by_operators() {
+        let types: Vec<_> = lex("12+3;")
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            types,
+            [
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::Semicolon,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_numbers() {
+        for (source, text) in [("12ab", "12ab"), ("2e", "2e"), ("3_000", "3_000")] {
+            match error(source) {
+                LexError::MalformedNumber { text: found, .. } => assert_eq!(found, text),
+                other => panic!("{}: {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn counts_columns_in_characters_and_spans_in_bytes() {
+        let tokens = lex("let é = \"ü→\";\n  naïve");
+        let spans: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.value.as_str(),
+                    token.span.line,
+                    token.span.column,
+                    token.span.start,
+                    token.span.end,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            spans,
+            [
+                ("let", 1, 1, 0, 3),
+                ("é", 1, 5, 4, 6),
+                ("=", 1, 7, 7, 8),
+                ("ü→", 1, 9, 9, 16),
+                (";", 1, 13, 16, 17),
+                ("naïve", 2, 3, 20, 26),
+                ("", 2, 8, 26, 26),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_errors_after_multibyte_characters() {
+        let error = error("\"日本\" @");
+
+        assert_eq!((error.span().column, error.span().start), (6, 9));
+    }
+
+    #[test]
+    fn keeps_doc_comments_and_skips_plain_ones() {
+        let tokens = lex("// plain\n/// doc\n//// ruler\nlet");
+        let found: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.token_type, token.value.as_str()))
+            .collect();
+
+        assert_eq!(
+            found,
+            [
+                (TokenType::DocComment, " doc"),
+                (TokenType::Let, "let"),
+                (TokenType::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_nested_block_comments() {
+        let types: Vec<_> = lex("1 /* a /* b */ c */ / 2")
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect();
+
+        assert_eq!(
+            types,
+            [
+                TokenType::Number,
+                TokenType::Slash,
+                TokenType::Number,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_block_comments() {
+        match error("let a = 1; /* outer /* inner */") {
+            LexError::UnterminatedComment { span } => assert_eq!(span.column, 12),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_escape_sequences() {
+        let tokens = lex(r#""a\n\t\r\0\\\"\u{48}\u{1F600}""#);
+
+        assert_eq!(tokens[0].value, "a\n\t\r\0\\\"H\u{1F600}");
+    }
+
+    #[test]
+    fn reports_invalid_escapes() {
+        for (source, sequence) in [
+            (r#""\q""#, r"\q"),
+            (r#""\u{110000}""#, r"\u{110000}"),
+            (r#""\u{D800}""#, r"\u{D800}"),
+            (r#""\u{0000041}""#, r"\u{0000041}"),
+            (r#""\u{}""#, r"\u{}"),
+            (r#""\u{41""#, r"\u{41"),
+            (r#""\u41""#, r"\u"),
+        ] {
+            match error(source) {
+                LexError::InvalidEscape {
+                    sequence: found, ..
+                } => assert_eq!(found, sequence),
+                other => panic!("{}: {:?}", source, other),
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_backslashes_in_raw_strings() {
+        let tokens = lex(r#"r"C:\new\u{41}" "after""#);
+
+        assert_eq!(tokens[0].value, r"C:\new\u{41}");
+        assert_eq!(tokens[1].value, "after");
     }
 }
@@ -1,5 +1,10 @@
+pub mod arithmetic;
 pub mod ast;
+pub mod builtins;
+pub mod checker;
+pub mod environment;
 pub mod evaluator;
 pub mod lexer;
 pub mod parser;
+pub mod span;
 pub mod token;
@@ -1,48 +1,87 @@
 use std::fmt;
 
-use super::ast::{self, Expression, ExpressionType};
+use crate::diagnostic::Diagnostic;
+
+use super::ast::{self, ExpressionType};
+use super::span::Span;
 use super::token::{Token, TokenType};
 
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub msg: String,
+    pub span: Span,
+    pub help: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-pub struct HmmgeError;
-
 impl ParseError {
-    pub fn new(expected: TokenType, gotten: TokenType) -> ParseError {
+    pub fn new(expected: TokenType, gotten: &Token) -> ParseError {
+        let msg = match expected {
+            TokenType::Invalid => format!("Unexpected token: {:?}", gotten.token_type),
+            _ => format!(
+                "Expected token: {:?} -> Gotten: {:?}",
+                expected, gotten.token_type
+            ),
+        };
+        let help = match expected {
+            TokenType::Semicolon => Some("statements must end with `;`".to_string()),
+            _ => None,
+        };
+
         ParseError {
-            msg: format!("Expected token: {:?} -> Gotten: {:?}", expected, gotten),
+            msg,
+            span: gotten.span,
+            help,
         }
     }
-}
 
-impl fmt::Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self.msg)
+    pub fn custom(msg: impl Into<String>, span: Span) -> ParseError {
+        ParseError {
+            msg: msg.into(),
+            span,
+            help: None,
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::new(self.msg.clone(), self.span);
+        match &self.help {
+            Some(help) => diagnostic.with_help(help.clone()),
+            None => diagnostic,
+        }
     }
 }
 
-impl fmt::Display for HmmgeError {
+impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "HmmgeError")
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.msg)
     }
 }
 
+impl std::error::Error for ParseError {}
+
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
-    in_function: bool,
+    errors: Vec<ParseError>,
+    /// Set while parsing the iterable of `for x in iterable { ... }`, from
+    /// the position where it starts. A `{` after a name there opens the loop
+    /// body rather than a struct literal, unless it is nested in brackets.
+    iterable_start: Option<usize>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
+        // Doc comments are only of interest to tooling, not to the grammar.
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| token.token_type != TokenType::DocComment)
+            .collect();
+
         Parser {
             tokens,
             position: 0,
-            in_function: false,
+            errors: Vec::new(),
+            iterable_start: None,
         }
     }
 }
@@ -50,116 +89,365 @@ impl Parser {
 impl Parser {
     fn current_token(&self) -> Token {
         if self.position >= self.tokens.len() {
-            return Token::new(TokenType::EOF, String::new());
+            return Token::new(TokenType::Eof, String::new(), self.eof_span());
         }
 
         self.tokens[self.position].clone()
     }
 
+    /// Span of the last token, used for nodes that run into the end of input.
+    fn eof_span(&self) -> Span {
+        match self.tokens.last() {
+            Some(token) => Span::new(
+                token.span.end,
+                token.span.end,
+                token.span.line,
+                token.span.column,
+            ),
+            None => Span::default(),
+        }
+    }
+
+    /// Type of the token `offset` positions ahead of the current one.
+    fn peek_type(&self, offset: usize) -> TokenType {
+        match self.tokens.get(self.position + offset) {
+            Some(token) => token.token_type,
+            None => TokenType::Eof,
+        }
+    }
+
+    /// Whether a `{` after a name can start a struct literal here.
+    fn struct_literal_allowed(&self) -> bool {
+        let Some(start) = self.iterable_start else {
+            return true;
+        };
+
+        let depth: i64 = self.tokens[start..self.position]
+            .iter()
+            .map(|token| match token.token_type {
+                TokenType::LBrace | TokenType::LParen | TokenType::LBracket => 1,
+                TokenType::RBrace | TokenType::RParen | TokenType::RBracket => -1,
+                _ => 0,
+            })
+            .sum();
+
+        depth > 0
+    }
+
+    /// Span of the most recently consumed token.
+    fn previous_span(&self) -> Span {
+        if self.position == 0 {
+            return self.current_token().span;
+        }
+
+        self.tokens[self.position - 1].span
+    }
+
     fn parse_factor(&mut self) -> Result<ast::Expression, ParseError> {
         let token = self.current_token();
 
+        match token.token_type {
+            // Folding `-` into the literal lets `-9223372036854775808` be written
+            // even though its magnitude doesn't fit in an `i64`. A postfix or `**`
+            // binds tighter than the `-`, so those are left as unary expressions.
+            TokenType::Minus
+                if self.peek_type(1) == TokenType::Number
+                    && !matches!(
+                        self.peek_type(2),
+                        TokenType::Power | TokenType::LParen | TokenType::LBracket | TokenType::Dot
+                    ) =>
+            {
+                let number = self.tokens[self.position + 1].clone();
+                self.position += 2;
+                Self::number_literal(&format!("-{}", number.value), token.span.to(number.span))
+            }
+            TokenType::Plus | TokenType::Minus | TokenType::Bang => {
+                self.position += 1;
+                let node = self.parse_factor()?;
+                let span = token.span.to(node.span);
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Unary(token, Box::new(node)),
+                    span,
+                })
+            }
+            _ => {
+                let node = self.parse_atom()?;
+                let node = self.parse_postfix(node)?;
+                self.parse_power(node)
+            }
+        }
+    }
+
+    /// `**` binds tighter than unary operators on its left and is right-associative,
+    /// so `-2 ** 2` is `-(2 ** 2)` and `2 ** 3 ** 2` is `2 ** (3 ** 2)`.
+    fn parse_power(&mut self, base: ast::Expression) -> Result<ast::Expression, ParseError> {
+        if self.current_token().token_type != TokenType::Power {
+            return Ok(base);
+        }
+
+        let operator = self.current_token();
+        self.position += 1;
+        let exponent = self.parse_factor()?;
+        let span = base.span.to(exponent.span);
+        Ok(ast::Expression {
+            expression_type: ExpressionType::Binary(Box::new(base), operator, Box::new(exponent)),
+            span,
+        })
+    }
+
+    /// An integer or float literal, told apart by a `.` or exponent in `text`.
+    fn number_literal(text: &str, span: Span) -> Result<ast::Expression, ParseError> {
+        let literal = if text.contains(['.', 'e', 'E']) {
+            let value = text.parse::<f64>().map_err(|_| {
+                ParseError::custom(format!("Invalid float literal `{}`", text), span)
+            })?;
+            ast::Literal::Float(value)
+        } else if let Ok(value) = text.parse::<i64>() {
+            ast::Literal::Int(value)
+        } else {
+            Self::big_literal(text, span)?
+        };
+
+        Ok(ast::Expression {
+            expression_type: ExpressionType::Literal(literal),
+            span,
+        })
+    }
+
+    #[cfg(feature = "bigint")]
+    fn big_literal(text: &str, span: Span) -> Result<ast::Literal, ParseError> {
+        text.parse()
+            .map(ast::Literal::BigInt)
+            .map_err(|_| ParseError::custom(format!("Invalid integer literal `{}`", text), span))
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    fn big_literal(text: &str, span: Span) -> Result<ast::Literal, ParseError> {
+        Err(ParseError::custom(
+            format!("Integer literal `{}` is out of range", text),
+            span,
+        ))
+    }
+
+    /// Parses calls, indexing and field access following an expression, e.g.
+    /// `make_adder(1)(2)`, `matrix[i][j]` or `line.start.x`.
+    fn parse_postfix(&mut self, mut node: ast::Expression) -> Result<ast::Expression, ParseError> {
+        loop {
+            let start = node.span;
+            let expression_type = match self.current_token().token_type {
+                TokenType::LParen => {
+                    self.advance_with_type(TokenType::LParen)?;
+                    let arguments = self.parse_function_arguments()?;
+                    ExpressionType::Call(Box::new(node), arguments)
+                }
+                TokenType::LBracket => {
+                    self.advance_with_type(TokenType::LBracket)?;
+                    let index = self.parse_expression()?;
+                    self.advance_with_type(TokenType::RBracket)?;
+                    ExpressionType::Index(Box::new(node), Box::new(index))
+                }
+                TokenType::Dot => {
+                    self.advance_with_type(TokenType::Dot)?;
+                    let field = self.advance_with_type(TokenType::Identif)?;
+                    ExpressionType::Field(Box::new(node), field.value)
+                }
+                _ => return Ok(node),
+            };
+
+            node = ast::Expression {
+                expression_type,
+                span: start.to(self.previous_span()),
+            };
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<ast::Expression, ParseError> {
+        let token = self.current_token();
+
         match token.token_type {
             TokenType::Number => {
+                self.position += 1;
+                Self::number_literal(&token.value, token.span)
+            }
+            TokenType::String => {
                 self.position += 1;
                 Ok(ast::Expression {
-                    expression_type: ExpressionType::Literal(token.value),
+                    expression_type: ExpressionType::Literal(ast::Literal::Str(token.value)),
+                    span: token.span,
                 })
             }
-            TokenType::Identif => {
+            // As with map literals, a `{` right after a name can't start a block.
+            TokenType::Identif
+                if self.peek_type(1) == TokenType::LBrace && self.struct_literal_allowed() =>
+            {
                 self.position += 1;
-                match self.current_token().token_type {
-                    TokenType::LParen => {
-                        self.position += 1;
-                        let arguments = self.parse_function_arguments()?;
-                        return Ok(ast::Expression {
-                            expression_type: ExpressionType::FunctionCall(
-                                token.value,
-                                Box::new(arguments),
-                            ),
-                        });
-                    }
-                    _ => {
-                        return Ok(ast::Expression {
-                            expression_type: ExpressionType::Variable(token.value),
-                        });
+                self.advance_with_type(TokenType::LBrace)?;
+                let mut fields = Vec::new();
+
+                while self.current_token().token_type != TokenType::RBrace {
+                    let field = self.advance_with_type(TokenType::Identif)?;
+                    self.advance_with_type(TokenType::Colon)?;
+                    let value = self.parse_expression()?;
+                    fields.push((field.value, value));
+
+                    if self.current_token().token_type != TokenType::RBrace {
+                        self.advance_with_type(TokenType::Comma)?;
                     }
                 }
+                self.advance_with_type(TokenType::RBrace)?;
+
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Struct(token.value, fields),
+                    span: token.span.to(self.previous_span()),
+                })
+            }
+            TokenType::Identif => {
+                self.position += 1;
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Variable(token.value),
+                    span: token.span,
+                })
+            }
+            TokenType::Func => {
+                self.advance_with_type(TokenType::Func)?;
+                self.advance_with_type(TokenType::LParen)?;
+                let params = self.parse_function_params()?;
+                let body = self.parse_block()?;
+
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Function(params, body),
+                    span: token.span.to(self.previous_span()),
+                })
             }
             TokenType::LParen => {
                 self.advance_with_type(TokenType::LParen)?;
                 let node = self.parse_expression()?;
                 self.advance_with_type(TokenType::RParen)?;
-                return Ok(node);
+                Ok(node)
             }
-            TokenType::Plus | TokenType::Minus => {
+            TokenType::LBracket => {
+                self.advance_with_type(TokenType::LBracket)?;
+                let mut elements = Vec::new();
+
+                while self.current_token().token_type != TokenType::RBracket {
+                    elements.push(self.parse_expression()?);
+                    if self.current_token().token_type != TokenType::RBracket {
+                        self.advance_with_type(TokenType::Comma)?;
+                    }
+                }
+                self.advance_with_type(TokenType::RBracket)?;
+
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::List(elements),
+                    span: token.span.to(self.previous_span()),
+                })
+            }
+            // Blocks only follow statement headers like `if (...)`, so a `{`
+            // in expression position always starts a map literal.
+            TokenType::LBrace => {
+                self.advance_with_type(TokenType::LBrace)?;
+                let mut entries = Vec::new();
+
+                while self.current_token().token_type != TokenType::RBrace {
+                    let key = self.parse_expression()?;
+                    self.advance_with_type(TokenType::Colon)?;
+                    let value = self.parse_expression()?;
+                    entries.push((key, value));
+
+                    if self.current_token().token_type != TokenType::RBrace {
+                        self.advance_with_type(TokenType::Comma)?;
+                    }
+                }
+                self.advance_with_type(TokenType::RBrace)?;
+
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Map(entries),
+                    span: token.span.to(self.previous_span()),
+                })
+            }
+            TokenType::True | TokenType::False => {
                 self.position += 1;
-                let node = self.parse_factor()?;
-                return Ok(ast::Expression {
-                    expression_type: ExpressionType::Unary(token, Box::new(node)),
-                });
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Literal(ast::Literal::Bool(
+                        token.token_type == TokenType::True,
+                    )),
+                    span: token.span,
+                })
             }
-            _ => Err(ParseError::new(TokenType::Invalid, TokenType::Invalid)),
+            // The operand is missing but the expression ends here, so the
+            // surrounding statement can still be parsed around an error node.
+            TokenType::RParen
+            | TokenType::Semicolon
+            | TokenType::Comma
+            | TokenType::RBrace
+            | TokenType::Eof => {
+                self.errors
+                    .push(ParseError::new(TokenType::Invalid, &token));
+                Ok(ast::Expression {
+                    expression_type: ExpressionType::Error,
+                    span: token.span,
+                })
+            }
+            _ => Err(ParseError::new(TokenType::Invalid, &token)),
         }
     }
 
-    fn parse_term(&mut self) -> Result<ast::Expression, ParseError> {
-        let mut node = self.parse_factor()?;
+    /// Parses a left-associative chain of `next` separated by any of `operators`.
+    fn parse_binary(
+        &mut self,
+        operators: &[TokenType],
+        next: fn(&mut Parser) -> Result<ast::Expression, ParseError>,
+    ) -> Result<ast::Expression, ParseError> {
+        let mut node = next(self)?;
 
-        while self.current_token().token_type == TokenType::Asterisk
-            || self.current_token().token_type == TokenType::Slash
-        {
+        while operators.contains(&self.current_token().token_type) {
             let tok = self.current_token();
             self.position += 1;
-            let right = self.parse_factor()?;
+
+            let right = next(self)?;
+            let span = node.span.to(right.span);
             node = ast::Expression {
                 expression_type: ExpressionType::Binary(Box::new(node), tok, Box::new(right)),
+                span,
             };
         }
 
-        return Ok(node);
+        Ok(node)
+    }
+
+    fn parse_term(&mut self) -> Result<ast::Expression, ParseError> {
+        self.parse_binary(
+            &[TokenType::Asterisk, TokenType::Slash, TokenType::Percent],
+            Parser::parse_factor,
+        )
     }
 
     fn parse_primary_expression(&mut self) -> Result<ast::Expression, ParseError> {
-        let mut node = self.parse_term()?;
+        self.parse_binary(&[TokenType::Plus, TokenType::Minus], Parser::parse_term)
+    }
 
-        while self.current_token().token_type == TokenType::Plus
-            || self.current_token().token_type == TokenType::Minus
-        {
-            let tok = self.current_token();
-            self.position += 1;
+    fn parse_comparison(&mut self) -> Result<ast::Expression, ParseError> {
+        self.parse_binary(
+            &[
+                TokenType::Gt,
+                TokenType::Lt,
+                TokenType::GtEq,
+                TokenType::LtEq,
+            ],
+            Parser::parse_primary_expression,
+        )
+    }
 
-            node = ast::Expression {
-                expression_type: ExpressionType::Binary(
-                    Box::new(node),
-                    tok,
-                    Box::new(self.parse_term()?),
-                ),
-            };
-        }
+    fn parse_equality(&mut self) -> Result<ast::Expression, ParseError> {
+        self.parse_binary(&[TokenType::Eq, TokenType::NotEq], Parser::parse_comparison)
+    }
 
-        Ok(node)
+    fn parse_and(&mut self) -> Result<ast::Expression, ParseError> {
+        self.parse_binary(&[TokenType::And], Parser::parse_equality)
     }
 
     fn parse_expression(&mut self) -> Result<ast::Expression, ParseError> {
-        let mut node = self.parse_primary_expression()?;
-
-        while self.current_token().token_type == TokenType::Gt
-            || self.current_token().token_type == TokenType::Lt
-        {
-            let tok = self.current_token();
-            self.position += 1;
-
-            node = ast::Expression {
-                expression_type: ExpressionType::Binary(
-                    Box::new(node),
-                    tok,
-                    Box::new(self.parse_primary_expression()?),
-                ),
-            };
-        }
-
-        Ok(node)
+        self.parse_binary(&[TokenType::Or], Parser::parse_and)
     }
 
     fn parse_declaration(&mut self) -> Result<ast::Declaration, ParseError> {
@@ -176,26 +464,100 @@ impl Parser {
 
         self.advance_with_type(TokenType::Semicolon)?;
 
-        return node;
+        node
     }
 
-    pub fn parse_program(&mut self) -> Result<ast::Program, ParseError> {
+    /// Parses the whole token stream, recovering from errors. Statements that
+    /// failed to parse are kept as `StatementType::Error` nodes and every error
+    /// encountered is returned alongside the program.
+    pub fn parse_program(&mut self) -> (ast::Program, Vec<ParseError>) {
         let mut statements: Vec<ast::Statement> = Vec::new();
 
-        while self.current_token().token_type != TokenType::EOF {
-            let statement = self.parse_statement()?;
+        while self.current_token().token_type != TokenType::Eof {
+            let statement = self.parse_statement_recovering(false);
             statements.push(statement);
         }
 
-        Ok(ast::Program { statements })
+        (
+            ast::Program { statements },
+            std::mem::take(&mut self.errors),
+        )
+    }
+
+    /// Parses a statement, and on failure records the error, skips to the next
+    /// statement boundary and returns an error node in its place.
+    fn parse_statement_recovering(&mut self, in_block: bool) -> ast::Statement {
+        let start_position = self.position;
+        let start = self.current_token().span;
+
+        match self.parse_statement() {
+            Ok(statement) => statement,
+            Err(error) => {
+                // An error node inside the expression may already have reported
+                // this token, don't report it twice.
+                let reported = self
+                    .errors
+                    .last()
+                    .is_some_and(|last| last.span == error.span);
+                if !reported {
+                    self.errors.push(error);
+                }
+                self.synchronize(in_block, start_position);
+
+                ast::Statement {
+                    statement_type: ast::StatementType::Error,
+                    span: start.to(self.previous_span()),
+                }
+            }
+        }
+    }
+
+    /// Panic-mode recovery: skips tokens until just after a `;`, or until a `}`
+    /// or a token that starts a new statement. Inside a block the closing `}`
+    /// is left for `parse_block`, at the top level a stray one is skipped.
+    /// Always makes progress past the statement that started at `start_position`.
+    fn synchronize(&mut self, in_block: bool, start_position: usize) {
+        if self.position == start_position {
+            match self.current_token().token_type {
+                TokenType::Eof | TokenType::Semicolon | TokenType::RBrace => {}
+                _ => self.position += 1,
+            }
+        }
+
+        loop {
+            match self.current_token().token_type {
+                TokenType::Eof => return,
+                TokenType::Semicolon => {
+                    self.position += 1;
+                    return;
+                }
+                TokenType::RBrace => {
+                    if !in_block {
+                        self.position += 1;
+                    }
+                    return;
+                }
+                TokenType::Let
+                | TokenType::If
+                | TokenType::While
+                | TokenType::For
+                | TokenType::Func
+                | TokenType::Struct
+                | TokenType::Return
+                | TokenType::Print => return,
+                _ => self.position += 1,
+            }
+        }
     }
 
     fn parse_block(&mut self) -> Result<Vec<ast::Statement>, ParseError> {
         let mut statements: Vec<ast::Statement> = Vec::new();
         self.advance_with_type(TokenType::LBrace)?;
 
-        while self.current_token().token_type != TokenType::RBrace {
-            let statement = self.parse_statement().unwrap();
+        while self.current_token().token_type != TokenType::RBrace
+            && self.current_token().token_type != TokenType::Eof
+        {
+            let statement = self.parse_statement_recovering(true);
             statements.push(statement);
         }
 
@@ -204,16 +566,16 @@ impl Parser {
         Ok(statements)
     }
 
-    fn parse_function_params(&mut self) -> Result<Vec<String>, ParseError> {
+    fn parse_function_params(&mut self) -> Result<Vec<ast::Param>, ParseError> {
         let mut params = Vec::new();
 
         if self.current_token().token_type != TokenType::RParen {
-            params.push(self.advance_with_type(TokenType::Identif)?.value);
+            params.push(self.parse_function_param()?);
         }
 
         while self.current_token().token_type != TokenType::RParen {
             self.advance_with_type(TokenType::Comma)?;
-            params.push(self.advance_with_type(TokenType::Identif)?.value);
+            params.push(self.parse_function_param()?);
         }
 
         self.advance_with_type(TokenType::RParen)?;
@@ -221,16 +583,60 @@ impl Parser {
         Ok(params)
     }
 
-    fn parse_function_arguments(&mut self) -> Result<Vec<Expression>, ParseError> {
+    /// Parses `name`, `name = default` or a final `...name`.
+    fn parse_function_param(&mut self) -> Result<ast::Param, ParseError> {
+        if self.current_token().token_type == TokenType::Ellipsis {
+            self.advance_with_type(TokenType::Ellipsis)?;
+            let name = self.advance_with_type(TokenType::Identif)?.value;
+
+            if self.current_token().token_type != TokenType::RParen {
+                return Err(ParseError::custom(
+                    "A rest parameter must be the last parameter",
+                    self.current_token().span,
+                ));
+            }
+
+            return Ok(ast::Param {
+                name,
+                default: None,
+                rest: true,
+            });
+        }
+
+        let name = self.advance_with_type(TokenType::Identif)?.value;
+        let default = match self.current_token().token_type {
+            TokenType::Assign => {
+                self.advance_with_type(TokenType::Assign)?;
+                Some(self.parse_expression()?)
+            }
+            _ => None,
+        };
+
+        Ok(ast::Param {
+            name,
+            default,
+            rest: false,
+        })
+    }
+
+    fn parse_function_arguments(&mut self) -> Result<Vec<ast::Argument>, ParseError> {
         let mut arguments = Vec::new();
 
         if self.current_token().token_type != TokenType::RParen {
-            arguments.push(self.parse_expression()?);
+            arguments.push(self.parse_function_argument()?);
         }
 
         while self.current_token().token_type != TokenType::RParen {
             self.advance_with_type(TokenType::Comma)?;
-            arguments.push(self.parse_expression()?);
+            arguments.push(self.parse_function_argument()?);
+
+            let last = &arguments[arguments.len() - 1];
+            if last.name.is_none() && arguments[arguments.len() - 2].name.is_some() {
+                return Err(ParseError::custom(
+                    "Positional argument after named arguments",
+                    last.value.span,
+                ));
+            }
         }
 
         self.advance_with_type(TokenType::RParen)?;
@@ -238,8 +644,26 @@ impl Parser {
         Ok(arguments)
     }
 
+    /// Parses `value` or `name: value`.
+    fn parse_function_argument(&mut self) -> Result<ast::Argument, ParseError> {
+        let name = match (self.current_token().token_type, self.peek_type(1)) {
+            (TokenType::Identif, TokenType::Colon) => {
+                let name = self.advance_with_type(TokenType::Identif)?.value;
+                self.advance_with_type(TokenType::Colon)?;
+                Some(name)
+            }
+            _ => None,
+        };
+
+        Ok(ast::Argument {
+            name,
+            value: self.parse_expression()?,
+        })
+    }
+
     pub fn parse_statement(&mut self) -> Result<ast::Statement, ParseError> {
         let token = self.current_token();
+        let start = token.span;
 
         match token.token_type {
             TokenType::Print => {
@@ -247,15 +671,17 @@ impl Parser {
                 let expr = self.parse_expression()?;
                 self.advance_with_type(TokenType::Semicolon)?;
 
-                return Ok(ast::Statement {
+                Ok(ast::Statement {
                     statement_type: ast::StatementType::Print(Box::new(expr)),
-                });
+                    span: start.to(self.previous_span()),
+                })
             }
             TokenType::Let => {
                 let assignment = self.parse_declaration()?;
-                return Ok(ast::Statement {
+                Ok(ast::Statement {
                     statement_type: ast::StatementType::Declaration(assignment),
-                });
+                    span: start.to(self.previous_span()),
+                })
             }
             TokenType::If => {
                 self.advance_with_type(TokenType::If)?;
@@ -269,59 +695,103 @@ impl Parser {
                     TokenType::Else => {
                         self.advance_with_type(TokenType::Else)?;
                         let else_statements = self.parse_block()?;
-                        return Ok(ast::Statement {
+                        Ok(ast::Statement {
                             statement_type: ast::StatementType::IfElseBlock(
                                 Box::new(expression),
-                                Box::new(statements),
-                                Box::new(else_statements),
+                                statements,
+                                else_statements,
                             ),
-                        });
-                    }
-                    _ => {
-                        return Ok(ast::Statement {
-                            statement_type: ast::StatementType::IfBlock(
-                                Box::new(expression),
-                                Box::new(statements),
-                            ),
-                        });
+                            span: start.to(self.previous_span()),
+                        })
                     }
+                    _ => Ok(ast::Statement {
+                        statement_type: ast::StatementType::IfBlock(
+                            Box::new(expression),
+                            statements,
+                        ),
+                        span: start.to(self.previous_span()),
+                    }),
                 }
             }
+            TokenType::While => {
+                self.advance_with_type(TokenType::While)?;
+                self.advance_with_type(TokenType::LParen)?;
+                let condition = self.parse_expression()?;
+                self.advance_with_type(TokenType::RParen)?;
+
+                let body = self.parse_block()?;
+
+                Ok(ast::Statement {
+                    statement_type: ast::StatementType::While(Box::new(condition), body),
+                    span: start.to(self.previous_span()),
+                })
+            }
+            TokenType::For => self.parse_for(),
+            TokenType::Break | TokenType::Continue => {
+                self.position += 1;
+                self.advance_with_type(TokenType::Semicolon)?;
+
+                let statement_type = match token.token_type {
+                    TokenType::Break => ast::StatementType::Break,
+                    _ => ast::StatementType::Continue,
+                };
+                Ok(ast::Statement {
+                    statement_type,
+                    span: start.to(self.previous_span()),
+                })
+            }
             TokenType::Func => {
                 self.advance_with_type(TokenType::Func)?;
                 let identif = self.advance_with_type(TokenType::Identif)?;
                 self.advance_with_type(TokenType::LParen)?;
 
                 let params = self.parse_function_params()?;
-                self.in_function = true;
                 let block = self.parse_block()?;
-                self.in_function = false;
 
-                return Ok(ast::Statement {
+                Ok(ast::Statement {
                     statement_type: ast::StatementType::FunctionDeclaration(
                         identif.value,
                         params,
-                        Box::new(block),
+                        block,
                     ),
-                });
+                    span: start.to(self.previous_span()),
+                })
             }
-            TokenType::Return => {
-                if !self.in_function {
-                    return Err(ParseError::new(TokenType::Invalid, TokenType::Invalid));
+            TokenType::Struct => {
+                self.advance_with_type(TokenType::Struct)?;
+                let identif = self.advance_with_type(TokenType::Identif)?;
+                self.advance_with_type(TokenType::LBrace)?;
+                let mut fields = Vec::new();
+
+                while self.current_token().token_type != TokenType::RBrace {
+                    fields.push(self.advance_with_type(TokenType::Identif)?.value);
+                    if self.current_token().token_type != TokenType::RBrace {
+                        self.advance_with_type(TokenType::Comma)?;
+                    }
                 }
+                self.advance_with_type(TokenType::RBrace)?;
+
+                Ok(ast::Statement {
+                    statement_type: ast::StatementType::StructDeclaration(identif.value, fields),
+                    span: start.to(self.previous_span()),
+                })
+            }
+            TokenType::Return => {
                 self.advance_with_type(TokenType::Return)?;
 
                 if self.current_token().token_type == TokenType::Semicolon {
                     self.advance_with_type(TokenType::Semicolon)?;
-                    return Ok(ast::Statement {
+                    Ok(ast::Statement {
                         statement_type: ast::StatementType::Return(None),
-                    });
+                        span: start.to(self.previous_span()),
+                    })
                 } else {
                     let expr = self.parse_expression()?;
                     self.advance_with_type(TokenType::Semicolon)?;
-                    return Ok(ast::Statement {
+                    Ok(ast::Statement {
                         statement_type: ast::StatementType::Return(Some(Box::new(expr))),
-                    });
+                        span: start.to(self.previous_span()),
+                    })
                 }
             }
             TokenType::Identif => {
@@ -329,52 +799,355 @@ impl Parser {
                 let token = self.current_token();
 
                 match token.token_type {
-                    TokenType::LParen => {
-                        self.advance_with_type(TokenType::LParen)?;
-                        let arguments = self.parse_function_arguments()?;
-                        self.advance_with_type(TokenType::Semicolon)?;
-
-                        return Ok(ast::Statement {
-                            statement_type: ast::StatementType::FunctionCall(
-                                identif.value,
-                                Box::new(arguments),
-                            ),
-                        });
-                    }
                     TokenType::Assign => {
                         self.advance_with_type(TokenType::Assign)?;
                         let expr = self.parse_expression()?;
                         self.advance_with_type(TokenType::Semicolon)?;
 
-                        return Ok(ast::Statement {
+                        Ok(ast::Statement {
                             statement_type: ast::StatementType::Assignment(ast::Assignment {
                                 identif: identif.value,
                                 value: expr,
                             }),
-                        });
+                            span: start.to(self.previous_span()),
+                        })
+                    }
+                    TokenType::LParen | TokenType::LBracket | TokenType::Dot => {
+                        // Parse the whole `xs[i].field` target or `make()(1)` call
+                        // as an expression.
+                        self.position -= 1;
+                        let target = self.parse_factor()?;
+
+                        if matches!(target.expression_type, ExpressionType::Call(..))
+                            && self.current_token().token_type == TokenType::Semicolon
+                        {
+                            self.advance_with_type(TokenType::Semicolon)?;
+                            return Ok(ast::Statement {
+                                statement_type: ast::StatementType::FunctionCall(Box::new(target)),
+                                span: start.to(self.previous_span()),
+                            });
+                        }
+
+                        self.advance_with_type(TokenType::Assign)?;
+                        let value = Box::new(self.parse_expression()?);
+                        self.advance_with_type(TokenType::Semicolon)?;
+
+                        let statement_type = match target.expression_type {
+                            ExpressionType::Index(collection, index) => {
+                                ast::StatementType::IndexAssignment(collection, index, value)
+                            }
+                            ExpressionType::Field(record, field) => {
+                                ast::StatementType::FieldAssignment(record, field, value)
+                            }
+                            _ => {
+                                return Err(ParseError::custom(
+                                    "Invalid assignment target",
+                                    target.span,
+                                ))
+                            }
+                        };
+                        Ok(ast::Statement {
+                            statement_type,
+                            span: start.to(self.previous_span()),
+                        })
                     }
-                    _ => Err(ParseError::new(TokenType::Invalid, TokenType::Invalid)),
+                    _ => Err(ParseError::new(TokenType::Invalid, &token)),
                 }
             }
-            _ => Err(ParseError::new(TokenType::Invalid, TokenType::Invalid)),
+            _ => Err(ParseError::new(TokenType::Invalid, &token)),
+        }
+    }
+
+    /// Parses `for x in iterable { ... }`, also written `for (x in iterable)`,
+    /// and the C-style `for (init; condition; step) { ... }`, where every
+    /// clause may be empty.
+    fn parse_for(&mut self) -> Result<ast::Statement, ParseError> {
+        let start = self.advance_with_type(TokenType::For)?.span;
+
+        if self.current_token().token_type == TokenType::Identif
+            && self.peek_type(1) == TokenType::In
+        {
+            let variable = self.advance_with_type(TokenType::Identif)?;
+            self.advance_with_type(TokenType::In)?;
+            self.iterable_start = Some(self.position);
+            let iterable = self.parse_expression();
+            self.iterable_start = None;
+
+            return self.parse_for_in(start, variable, iterable?);
+        }
+
+        self.advance_with_type(TokenType::LParen)?;
+
+        if self.current_token().token_type == TokenType::Identif
+            && self.peek_type(1) == TokenType::In
+        {
+            let variable = self.advance_with_type(TokenType::Identif)?;
+            self.advance_with_type(TokenType::In)?;
+            let iterable = self.parse_expression()?;
+            self.advance_with_type(TokenType::RParen)?;
+
+            return self.parse_for_in(start, variable, iterable);
+        }
+
+        let init = match self.current_token().token_type {
+            TokenType::Semicolon => {
+                self.advance_with_type(TokenType::Semicolon)?;
+                None
+            }
+            TokenType::Let => Some(Box::new(self.parse_statement()?)),
+            _ => {
+                let init = self.parse_assignment_clause()?;
+                self.advance_with_type(TokenType::Semicolon)?;
+                Some(Box::new(init))
+            }
+        };
+
+        let condition = match self.current_token().token_type {
+            TokenType::Semicolon => None,
+            _ => Some(Box::new(self.parse_expression()?)),
+        };
+        self.advance_with_type(TokenType::Semicolon)?;
+
+        let step = match self.current_token().token_type {
+            TokenType::RParen => None,
+            _ => Some(Box::new(self.parse_assignment_clause()?)),
+        };
+        self.advance_with_type(TokenType::RParen)?;
+
+        let body = self.parse_block()?;
+
+        Ok(ast::Statement {
+            statement_type: ast::StatementType::For(init, condition, step, body),
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    fn parse_for_in(
+        &mut self,
+        start: Span,
+        variable: Token,
+        iterable: ast::Expression,
+    ) -> Result<ast::Statement, ParseError> {
+        let body = self.parse_block()?;
+
+        Ok(ast::Statement {
+            statement_type: ast::StatementType::ForIn(variable.value, Box::new(iterable), body),
+            span: start.to(self.previous_span()),
+        })
+    }
+
+    /// Parses `identif = expression` without the trailing `;`, as used in
+    /// the clauses of a `for` loop.
+    fn parse_assignment_clause(&mut self) -> Result<ast::Statement, ParseError> {
+        let identif = self.advance_with_type(TokenType::Identif)?;
+        self.advance_with_type(TokenType::Assign)?;
+        let expr = self.parse_expression()?;
+
+        Ok(ast::Statement {
+            statement_type: ast::StatementType::Assignment(ast::Assignment {
+                identif: identif.value,
+                value: expr,
+            }),
+            span: identif.span.to(self.previous_span()),
+        })
+    }
+
+    /// Parses input consisting of a single expression with an optional trailing
+    /// `;`, which the REPL echoes instead of running as a statement.
+    pub fn parse_lone_expression(&mut self) -> Result<ast::Expression, ParseError> {
+        let expression = self.parse_expression()?;
+        if let Some(error) = self.errors.pop() {
+            return Err(error);
+        }
+
+        if self.current_token().token_type == TokenType::Semicolon {
+            self.advance_with_type(TokenType::Semicolon)?;
         }
+        self.advance_with_type(TokenType::Eof)?;
+
+        Ok(expression)
     }
 
-    pub fn parse(&mut self) -> Result<ast::Program, ParseError> {
-        self.parse_program()
+    pub fn parse(&mut self) -> Result<ast::Program, Vec<ParseError>> {
+        let (program, errors) = self.parse_program();
+
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
     }
 
     fn advance_with_type(&mut self, token_type: TokenType) -> Result<Token, ParseError> {
-        if self.position >= self.tokens.len() {
-            return Err(ParseError::new(token_type, TokenType::EOF));
+        let token = self.current_token();
+        if token.token_type == token_type {
+            self.position += 1;
+            Ok(token)
         } else {
-            let token = self.tokens[self.position].clone();
-            if token.token_type == token_type {
-                self.position += 1;
-                return Ok(token);
-            } else {
-                return Err(ParseError::new(token_type, token.token_type));
-            }
+            Err(ParseError::new(token_type, &token))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::lexer::Lexer;
+
+    fn parse(source: &str) -> (ast::Program, Vec<ParseError>) {
+        let tokens = Lexer::new(source).lex().expect("source should lex");
+        Parser::new(tokens).parse_program()
+    }
+
+    fn messages(source: &str) -> Vec<(usize, String)> {
+        parse(source)
+            .1
+            .into_iter()
+            .map(|error| (error.span.line, error.msg))
+            .collect()
+    }
+
+    fn literal(source: &str) -> ast::Literal {
+        let tokens = Lexer::new(source).lex().expect("source should lex");
+        match Parser::new(tokens).parse_lone_expression() {
+            Ok(ast::Expression {
+                expression_type: ExpressionType::Literal(literal),
+                ..
+            }) => literal,
+            other => panic!("expected a literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_every_broken_statement() {
+        let (program, errors) = parse("let a = ;\nprint 1;\nlet = 2;\nprint 3;\n");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!((errors[0].span.line, errors[1].span.line), (1, 3));
+        // The missing value becomes an error node, so the `let` itself survives.
+        let statements: Vec<_> = program
+            .statements
+            .iter()
+            .map(|statement| &statement.statement_type)
+            .collect();
+        assert!(matches!(
+            statements[..],
+            [
+                ast::StatementType::Declaration(ast::Declaration {
+                    value: ast::Expression {
+                        expression_type: ExpressionType::Error,
+                        ..
+                    },
+                    ..
+                }),
+                ast::StatementType::Print(_),
+                ast::StatementType::Error,
+                ast::StatementType::Print(_),
+            ]
+        ));
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let errors = messages("if (true) {\n  let = 1;\n  print 2;\n}\nprint (;\n");
+
+        assert_eq!(
+            errors,
+            [
+                (2, "Expected token: Identif -> Gotten: Assign".to_string()),
+                (5, "Unexpected token: Semicolon".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_stray_closing_brace() {
+        let (program, errors) = parse("}\nprint 1;\n}}\n");
+
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(
+            program
+                .statements
+                .last()
+                .map(|statement| &statement.statement_type),
+            Some(ast::StatementType::Error)
+        ));
+        assert!(program
+            .statements
+            .iter()
+            .any(|statement| matches!(statement.statement_type, ast::StatementType::Print(_))));
+    }
+
+    #[test]
+    fn reports_error_node_once() {
+        let errors = messages("print 1 + ;\n");
+
+        assert_eq!(errors, [(1, "Unexpected token: Semicolon".to_string())]);
+    }
+
+    #[test]
+    fn parses_for_in_with_or_without_parentheses() {
+        for source in [
+            "for x in items { print x; }",
+            "for (x in items) { print x; }",
+            "for x in [P { a: 1 }] { print x; }",
+        ] {
+            let (program, errors) = parse(source);
+
+            assert!(errors.is_empty(), "{}: {:?}", source, errors);
+            assert!(matches!(
+                program.statements[..],
+                [ast::Statement {
+                    statement_type: ast::StatementType::ForIn(..),
+                    ..
+                }]
+            ));
+        }
+    }
+
+    #[test]
+    fn folds_negative_literals() {
+        assert_eq!(literal("-9223372036854775808"), ast::Literal::Int(i64::MIN));
+        assert_eq!(literal("-2.5"), ast::Literal::Float(-2.5));
+    }
+
+    #[test]
+    fn keeps_unary_minus_below_power() {
+        let tokens = Lexer::new("-2 ** 2").lex().unwrap();
+        let expression = Parser::new(tokens).parse_lone_expression().unwrap();
+
+        assert!(matches!(
+            expression.expression_type,
+            ExpressionType::Unary(_, ref operand)
+                if matches!(operand.expression_type, ExpressionType::Binary(..))
+        ));
+    }
+
+    #[cfg(not(feature = "bigint"))]
+    #[test]
+    fn rejects_out_of_range_literals() {
+        let errors = messages("print 9223372036854775808;\n");
+
+        assert_eq!(
+            errors,
+            [(
+                1,
+                "Integer literal `9223372036854775808` is out of range".to_string()
+            )]
+        );
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn parses_out_of_range_literals_as_big_integers() {
+        for text in ["9223372036854775808", "-99999999999999999999"] {
+            assert_eq!(
+                literal(text),
+                ast::Literal::BigInt(text.parse().unwrap()),
+                "{}",
+                text
+            );
         }
+        assert_eq!(literal("-9223372036854775808"), ast::Literal::Int(i64::MIN));
     }
 }
@@ -1,3 +1,5 @@
+use super::span::Span;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenType {
     // types
@@ -7,9 +9,17 @@ pub enum TokenType {
     Let,
     If,
     Else,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
     Func,
+    Struct,
     Return,
     Print,
+    True,
+    False,
     // identif
     Identif,
     // symbols
@@ -17,7 +27,12 @@ pub enum TokenType {
     LParen,
     RBrace,
     LBrace,
+    RBracket,
+    LBracket,
     Semicolon,
+    Colon,
+    Dot,
+    Ellipsis,
     Assign,
     Comma,
     // operators
@@ -25,20 +40,37 @@ pub enum TokenType {
     Plus,
     Asterisk,
     Slash,
+    Percent,
+    Power,
     Gt,
     Lt,
+    GtEq,
+    LtEq,
+    Eq,
+    NotEq,
+    And,
+    Or,
+    Bang,
+    // trivia
+    DocComment,
     //
-    EOF,
+    Eof,
     Invalid,
 }
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub value: String,
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, value: String) -> Self {
-        Token { token_type, value }
+    pub fn new(token_type: TokenType, value: String, span: Span) -> Self {
+        Token {
+            token_type,
+            value,
+            span,
+        }
     }
 }
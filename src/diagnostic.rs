@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use crate::compiler::span::Span;
+
+/// An error report that can be rendered against the source it refers to.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Renders the diagnostic rustc-style:
+    ///
+    /// ```text
+    /// error: Expected token: Semicolon -> Gotten: Eof
+    ///  --> test.ilu:2:10
+    ///   |
+    /// 2 | let x = 5
+    ///   |          ^
+    ///   = help: statements must end with `;`
+    /// ```
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        let (line_number, column, line) = self.locate(source);
+        let underline = self.underline_width(source, column, line);
+        let gutter = " ".repeat(line_number.to_string().len());
+        let padding: String = line
+            .chars()
+            .take(column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let mut out = String::new();
+        let _ = writeln!(out, "error: {}", self.message);
+        let _ = writeln!(
+            out,
+            "{}--> {}:{}:{}",
+            gutter, file_name, line_number, column
+        );
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", line_number, line);
+        let _ = writeln!(out, "{} | {}{}", gutter, padding, "^".repeat(underline));
+        if let Some(help) = &self.help {
+            let _ = writeln!(out, "{} = help: {}", gutter, help);
+        }
+
+        out
+    }
+
+    /// Finds the line the diagnostic points at. Spans at the end of input are
+    /// moved to just after the last non-empty line, which is where the missing
+    /// code would have been.
+    fn locate<'s>(&self, source: &'s str) -> (usize, usize, &'s str) {
+        if self.span.start >= source.len() {
+            if let Some((index, line)) = source
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .last()
+            {
+                let line = line.trim_end();
+                return (index + 1, line.chars().count() + 1, line);
+            }
+        }
+
+        let line = source.lines().nth(self.span.line - 1).unwrap_or("");
+        (self.span.line, self.span.column.max(1), line)
+    }
+
+    fn underline_width(&self, source: &str, column: usize, line: &str) -> usize {
+        let covered = source
+            .get(self.span.start..self.span.end)
+            .map(|text| text.lines().next().unwrap_or("").chars().count())
+            .unwrap_or(0);
+        let remaining = line.chars().count().saturating_sub(column - 1);
+
+        covered.min(remaining).max(1)
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io;
+
+use crate::compiler::{
+    checker::SemanticError, evaluator::RuntimeError, lexer::LexError, parser::ParseError,
+};
+use crate::diagnostic::Diagnostic;
+
+/// Every way running an Ilumnia program can fail.
+#[derive(Debug)]
+pub enum IlumniaError {
+    Io {
+        path: String,
+        error: io::Error,
+    },
+    /// Writing to stdout failed.
+    Output(io::Error),
+    Lex(LexError),
+    Parse(Vec<ParseError>),
+    Semantic(Vec<SemanticError>),
+    Runtime(RuntimeError),
+}
+
+impl IlumniaError {
+    /// Process exit code for this category of error, so callers can tell
+    /// a syntax error from a runtime failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            IlumniaError::Io { .. } | IlumniaError::Output(_) => 1,
+            IlumniaError::Lex(_) => 3,
+            IlumniaError::Parse(_) => 4,
+            IlumniaError::Semantic(_) => 5,
+            IlumniaError::Runtime(_) => 6,
+        }
+    }
+
+    /// Whether the error is stdout having been closed, as by `| head`, which
+    /// isn't a failure of the program.
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            IlumniaError::Output(error) => error.kind() == io::ErrorKind::BrokenPipe,
+            IlumniaError::Runtime(RuntimeError::Output { kind, .. }) => {
+                *kind == io::ErrorKind::BrokenPipe
+            }
+            _ => false,
+        }
+    }
+
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        match self {
+            IlumniaError::Io { .. } | IlumniaError::Output(_) => Vec::new(),
+            IlumniaError::Lex(error) => vec![error.to_diagnostic()],
+            IlumniaError::Parse(errors) => errors.iter().map(|e| e.to_diagnostic()).collect(),
+            IlumniaError::Semantic(errors) => errors.iter().map(|e| e.to_diagnostic()).collect(),
+            IlumniaError::Runtime(error) => vec![error.to_diagnostic()],
+        }
+    }
+
+    /// Renders every error against `source`, see `Diagnostic::render`.
+    pub fn render(&self, file_name: &str, source: &str) -> String {
+        match self {
+            IlumniaError::Io { .. } | IlumniaError::Output(_) => format!("error: {}\n", self),
+            _ => self
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| diagnostic.render(file_name, source))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for IlumniaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IlumniaError::Io { path, error } => write!(f, "Could not read `{}`: {}", path, error),
+            IlumniaError::Output(error) => write!(f, "Could not write output: {}", error),
+            IlumniaError::Lex(error) => write!(f, "{}", error),
+            IlumniaError::Parse(errors) => write_all(f, errors),
+            IlumniaError::Semantic(errors) => write_all(f, errors),
+            IlumniaError::Runtime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+fn write_all<T: fmt::Display>(f: &mut fmt::Formatter, errors: &[T]) -> fmt::Result {
+    for (index, error) in errors.iter().enumerate() {
+        if index > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{}", error)?;
+    }
+
+    Ok(())
+}
+
+impl std::error::Error for IlumniaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IlumniaError::Io { error, .. } | IlumniaError::Output(error) => Some(error),
+            IlumniaError::Lex(error) => Some(error),
+            IlumniaError::Parse(errors) => errors.first().map(|e| e as _),
+            IlumniaError::Semantic(errors) => errors.first().map(|e| e as _),
+            IlumniaError::Runtime(error) => Some(error),
+        }
+    }
+}
+
+impl From<LexError> for IlumniaError {
+    fn from(error: LexError) -> Self {
+        IlumniaError::Lex(error)
+    }
+}
+
+impl From<Vec<ParseError>> for IlumniaError {
+    fn from(errors: Vec<ParseError>) -> Self {
+        IlumniaError::Parse(errors)
+    }
+}
+
+impl From<Vec<SemanticError>> for IlumniaError {
+    fn from(errors: Vec<SemanticError>) -> Self {
+        IlumniaError::Semantic(errors)
+    }
+}
+
+impl From<RuntimeError> for IlumniaError {
+    fn from(error: RuntimeError) -> Self {
+        IlumniaError::Runtime(error)
+    }
+}
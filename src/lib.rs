@@ -0,0 +1,4 @@
+pub mod compiler;
+pub mod diagnostic;
+pub mod error;
+pub mod repl;
@@ -1,27 +1,229 @@
-mod error;
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::process;
+use std::rc::Rc;
 
-mod compiler;
+use ilumnia::compiler::ast::Program;
+use ilumnia::compiler::evaluator::{Value, ValueType};
+use ilumnia::compiler::{checker::Checker, evaluator::Evaluator, lexer::Lexer, parser::Parser};
+use ilumnia::error::IlumniaError;
+use ilumnia::repl::Repl;
 
-use compiler::{
-    evaluator::Evaluator,
-    lexer::Lexer,
-    parser::{ParseError, Parser},
-};
+const USAGE: &str = "\
+Usage:
+  ilumnia                       start the REPL
+  ilumnia <file> [args...]      same as `ilumnia run`
+  ilumnia run <file> [args...]  run a program; `args` holds the extra arguments
+  ilumnia check <file>          parse and check a program without running it
+  ilumnia tokens <file>         print the tokens of a program
+  ilumnia ast <file>            print the syntax tree of a program
+  ilumnia eval -e <code> [args...]
+                                run code given on the command line
+  ilumnia repl                  start the REPL
 
-fn main() -> Result<(), ParseError> {
-    let args = std::env::args().collect::<Vec<String>>();
+A <file> of `-` reads the program from stdin.
 
-    let input = std::fs::read_to_string(&args[1]).unwrap();
-    let mut lexer = Lexer::new(&input);
+Options:
+  -h, --help  show this message
 
-    let tokens = lexer.lex();
+Exit codes:
+  0 success, 1 file error, 2 usage error, 3 lex error, 4 parse error,
+  5 semantic error, 6 runtime error
+";
 
-    let mut parser = Parser::new(tokens);
-    let program = parser.parse()?;
+/// Exit code for command line mistakes, alongside `IlumniaError::exit_code`.
+const USAGE_EXIT_CODE: i32 = 2;
+
+enum Command {
+    Run { path: String, args: Vec<String> },
+    Check { path: String },
+    Tokens { path: String },
+    Ast { path: String },
+    Eval { code: String, args: Vec<String> },
+    Repl,
+    Help,
+}
+
+fn is_help(arg: &str) -> bool {
+    arg == "-h" || arg == "--help"
+}
+
+/// Parses the command line. Arguments after a program's file or code belong
+/// to the program, so `ilumnia run tool.ilu --help` passes `--help` on.
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((first, rest)) = args.split_first() else {
+        return Ok(Command::Repl);
+    };
+
+    match first.as_str() {
+        "help" => Ok(Command::Help),
+        arg if is_help(arg) => Ok(Command::Help),
+        "repl" | "run" | "check" | "tokens" | "ast" | "eval"
+            if rest.first().is_some_and(|arg| is_help(arg)) =>
+        {
+            Ok(Command::Help)
+        }
+        "repl" => match rest.first() {
+            None => Ok(Command::Repl),
+            Some(extra) => Err(format!("Unexpected argument `{}`", extra)),
+        },
+        "run" => match rest.split_first() {
+            Some((path, args)) => Ok(Command::Run {
+                path: path.clone(),
+                args: args.to_vec(),
+            }),
+            None => Err("`run` needs a file".to_string()),
+        },
+        "check" | "tokens" | "ast" => {
+            let path = match rest {
+                [path] => path.clone(),
+                [] => return Err(format!("`{}` needs a file", first)),
+                [_, extra, ..] => return Err(format!("Unexpected argument `{}`", extra)),
+            };
+            Ok(match first.as_str() {
+                "check" => Command::Check { path },
+                "tokens" => Command::Tokens { path },
+                _ => Command::Ast { path },
+            })
+        }
+        "eval" => match rest {
+            [flag, code, args @ ..] if flag == "-e" => Ok(Command::Eval {
+                code: code.clone(),
+                args: args.to_vec(),
+            }),
+            _ => Err("`eval` needs code, as in `eval -e '<code>'`".to_string()),
+        },
+        option if option.starts_with('-') && option != "-" => {
+            Err(format!("Unknown option `{}`", option))
+        }
+        _ => Ok(Command::Run {
+            path: first.clone(),
+            args: rest.to_vec(),
+        }),
+    }
+}
+
+/// Reads a program, from stdin when `path` is `-`. Returns the name to report
+/// errors under along with the source.
+fn read_source(path: &str) -> Result<(String, String), IlumniaError> {
+    let (name, result) = if path == "-" {
+        let mut source = String::new();
+        let result = io::stdin().read_to_string(&mut source).map(|_| source);
+        ("<stdin>".to_string(), result)
+    } else {
+        (path.to_string(), std::fs::read_to_string(path))
+    };
+
+    match result {
+        Ok(source) => Ok((name, source)),
+        Err(error) => Err(IlumniaError::Io { path: name, error }),
+    }
+}
+
+fn parse(source: &str) -> Result<Program, IlumniaError> {
+    let tokens = Lexer::new(source).lex()?;
+    Ok(Parser::new(tokens).parse()?)
+}
+
+fn check(source: &str) -> Result<Program, IlumniaError> {
+    let program = parse(source)?;
+    Checker::new()
+        .with_globals(["args".to_string()])
+        .check(&program)?;
+
+    Ok(program)
+}
+
+fn run(source: &str, args: &[String]) -> Result<(), IlumniaError> {
+    let program = check(source)?;
 
     let mut evaluator = Evaluator::new(program);
+    let args = args
+        .iter()
+        .map(|arg| Value::new(ValueType::String(arg.clone())))
+        .collect();
+    evaluator.globals.borrow_mut().define(
+        "args".to_string(),
+        Value::new(ValueType::List(Rc::new(RefCell::new(args)))),
+    );
+    evaluator.evaluate()?;
+
+    Ok(())
+}
 
-    evaluator.evaluate();
+fn tokens(source: &str) -> Result<(), IlumniaError> {
+    let mut out = io::stdout().lock();
+    for token in Lexer::new(source).lex()? {
+        writeln!(
+            out,
+            "{}:{} {:?} {:?}",
+            token.span.line, token.span.column, token.token_type, token.value
+        )
+        .map_err(IlumniaError::Output)?;
+    }
 
     Ok(())
 }
+
+fn ast(source: &str) -> Result<(), IlumniaError> {
+    let program = parse(source)?;
+    writeln!(io::stdout().lock(), "{:#?}", program).map_err(IlumniaError::Output)
+}
+
+/// Runs `action` on the program at `path`, rendering any error, and returns
+/// the exit code.
+fn with_source(path: &str, action: impl FnOnce(&str) -> Result<(), IlumniaError>) -> i32 {
+    let (name, source) = match read_source(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprint!("{}", error.render(path, ""));
+            return error.exit_code();
+        }
+    };
+
+    report(&name, &source, action(&source))
+}
+
+fn report(name: &str, source: &str, result: Result<(), IlumniaError>) -> i32 {
+    match result {
+        Ok(()) => 0,
+        Err(error) if error.is_broken_pipe() => 0,
+        Err(error) => {
+            eprint!("{}", error.render(name, source));
+            error.exit_code()
+        }
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprint!("error: {}\n\n{}", message, USAGE);
+            process::exit(USAGE_EXIT_CODE);
+        }
+    };
+
+    let exit_code = match command {
+        Command::Run { path, args } => with_source(&path, |source| run(source, &args)),
+        Command::Check { path } => with_source(&path, |source| check(source).map(|_| ())),
+        Command::Tokens { path } => with_source(&path, tokens),
+        Command::Ast { path } => with_source(&path, ast),
+        Command::Eval { code, args } => report("<eval>", &code, run(&code, &args)),
+        Command::Repl => match Repl::new().run() {
+            Ok(()) => 0,
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => 0,
+            Err(error) => {
+                eprintln!("error: {}", error);
+                1
+            }
+        },
+        Command::Help => {
+            print!("{}", USAGE);
+            0
+        }
+    };
+
+    process::exit(exit_code);
+}
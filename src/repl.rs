@@ -0,0 +1,352 @@
+//! The interactive prompt, started by running `ilumnia` without a file.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::compiler::ast::{Program, Statement, StatementType};
+use crate::compiler::checker::Checker;
+use crate::compiler::evaluator::Evaluator;
+use crate::compiler::lexer::{LexError, Lexer};
+use crate::compiler::parser::Parser;
+use crate::compiler::span::Span;
+use crate::compiler::token::{Token, TokenType};
+use crate::diagnostic::Diagnostic;
+use crate::error::IlumniaError;
+
+const HELP: &str = "\
+Enter statements to run them, or an expression to print its value.
+Input continues on the next line while a string, comment or bracket is open.
+
+Commands:
+  :help         show this message
+  :reset        forget every binding and function
+  :load <file>  run a file, keeping its bindings
+  :quit         leave the REPL (Ctrl-D works too)
+";
+
+/// Name errors are reported under, in place of a file name.
+const SOURCE_NAME: &str = "<repl>";
+
+pub struct Repl {
+    evaluator: Evaluator,
+    /// Everything entered or loaded so far. New input is lexed as if it
+    /// followed it, so spans from functions defined earlier still point at
+    /// the right text.
+    session: String,
+    /// Number of lines in `session`, which always ends with a newline.
+    lines: usize,
+    files: Vec<LoadedFile>,
+}
+
+/// A file run with `:load`, and where its text sits in the session.
+struct LoadedFile {
+    path: String,
+    start: usize,
+    end: usize,
+    /// Line of the session the file starts on.
+    line: usize,
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            evaluator: Evaluator::new(Program {
+                statements: Vec::new(),
+            }),
+            session: String::new(),
+            lines: 0,
+            files: Vec::new(),
+        }
+    }
+
+    /// Reads and runs input from stdin until it ends or `:quit` is entered.
+    /// Prompts are only shown when stdin is a terminal.
+    pub fn run(&mut self) -> io::Result<()> {
+        let interactive = io::stdin().is_terminal();
+        if interactive {
+            println!("Ilumnia REPL. Type :help for help.");
+        }
+
+        let mut input = String::new();
+        let mut lines = io::stdin().lock().lines();
+        loop {
+            if interactive {
+                print!("{}", if input.is_empty() { "> " } else { "... " });
+                io::stdout().flush()?;
+            }
+
+            let Some(line) = lines.next() else {
+                if interactive {
+                    println!();
+                }
+                return Ok(());
+            };
+            let line = line?;
+
+            if input.is_empty() {
+                match line.trim() {
+                    "" => continue,
+                    ":quit" => return Ok(()),
+                    command if command.starts_with(':') => {
+                        self.command(command)?;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            input.push_str(&line);
+            input.push('\n');
+            if is_complete(&input) {
+                self.eval(&std::mem::take(&mut input))?;
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str) -> io::Result<()> {
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name {
+            ":help" => write!(io::stdout(), "{}", HELP)?,
+            ":reset" => {
+                *self = Repl::new();
+                writeln!(io::stdout(), "Session reset.")?;
+            }
+            ":load" if argument.is_empty() => eprintln!("error: usage: :load <file>"),
+            ":load" => self.load(argument)?,
+            _ => eprintln!("error: Unknown command `{}`, see :help", name),
+        }
+
+        Ok(())
+    }
+
+    fn load(&mut self, path: &str) -> io::Result<()> {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                let error = IlumniaError::Io {
+                    path: path.to_string(),
+                    error,
+                };
+                eprint!("{}", error.render(path, ""));
+                return Ok(());
+            }
+        };
+
+        let (start, line) = self.append(&source);
+        self.files.push(LoadedFile {
+            path: path.to_string(),
+            start,
+            end: self.session.len(),
+            line,
+        });
+
+        let result = self
+            .lex(start, line)
+            .and_then(|tokens| Ok(Parser::new(tokens).parse()?))
+            .and_then(|program| self.check_and_execute(&program));
+        self.finish(start, line, result)
+    }
+
+    fn eval(&mut self, input: &str) -> io::Result<()> {
+        let (start, line) = self.append(input);
+
+        let result = self
+            .lex(start, line)
+            .and_then(|tokens| self.parse(tokens))
+            .and_then(|program| self.check_and_execute(&program));
+        self.finish(start, line, result)
+    }
+
+    /// Adds `text` to the session, returning the byte offset and line it
+    /// starts at.
+    fn append(&mut self, text: &str) -> (usize, usize) {
+        let (start, line) = (self.session.len(), self.lines + 1);
+        self.session.push_str(text);
+        if !self.session.ends_with('\n') {
+            self.session.push('\n');
+        }
+        self.lines += self.session[start..].matches('\n').count();
+
+        (start, line)
+    }
+
+    /// Lexes the session text from `start` on, which `append` just added.
+    fn lex(&self, start: usize, line: usize) -> Result<Vec<Token>, IlumniaError> {
+        Ok(Lexer::continuing(&self.session[start..], start, line).lex()?)
+    }
+
+    /// Reports the error of the text added at `start`, if any. Text that
+    /// doesn't lex is dropped again, as it would break lexing what follows.
+    /// Only a closed stdout is passed on, as it ends the session.
+    fn finish(
+        &mut self,
+        start: usize,
+        line: usize,
+        result: Result<(), IlumniaError>,
+    ) -> io::Result<()> {
+        let Err(error) = result else {
+            return Ok(());
+        };
+        if error.is_broken_pipe() {
+            return Err(io::ErrorKind::BrokenPipe.into());
+        }
+
+        for diagnostic in error.diagnostics() {
+            eprint!("{}", self.render(diagnostic));
+        }
+        if matches!(error, IlumniaError::Lex(_)) {
+            self.session.truncate(start);
+            self.lines = line - 1;
+            self.files.retain(|file| file.start < start);
+        }
+
+        Ok(())
+    }
+
+    /// Renders a diagnostic against the loaded file it points into, or
+    /// against the session.
+    fn render(&self, diagnostic: Diagnostic) -> String {
+        let span = diagnostic.span;
+        let Some(file) = self
+            .files
+            .iter()
+            .find(|file| (file.start..file.end).contains(&span.start))
+        else {
+            return diagnostic.render(SOURCE_NAME, &self.session);
+        };
+
+        let span = Span::new(
+            span.start - file.start,
+            span.end - file.start,
+            span.line - file.line + 1,
+            span.column,
+        );
+        Diagnostic { span, ..diagnostic }.render(&file.path, &self.session[file.start..file.end])
+    }
+
+    /// Parses input as statements, falling back to a lone expression, which is
+    /// run as a `print` of its value.
+    fn parse(&self, tokens: Vec<Token>) -> Result<Program, IlumniaError> {
+        let is_statement = looks_like_statement(&tokens);
+        let errors = match Parser::new(tokens.clone()).parse() {
+            Ok(program) => return Ok(program),
+            Err(errors) => errors,
+        };
+
+        match Parser::new(tokens).parse_lone_expression() {
+            Ok(expression) => {
+                let span = expression.span;
+                Ok(Program {
+                    statements: vec![Statement {
+                        statement_type: StatementType::Print(Box::new(expression)),
+                        span,
+                    }],
+                })
+            }
+            // Report whichever reading the input was going for.
+            Err(error) if !is_statement => Err(IlumniaError::Parse(vec![error])),
+            Err(_) => Err(IlumniaError::Parse(errors)),
+        }
+    }
+
+    fn check_and_execute(&mut self, program: &Program) -> Result<(), IlumniaError> {
+        let globals: Vec<String> = self.evaluator.globals.borrow().names().cloned().collect();
+        Checker::new()
+            .with_globals(globals)
+            .in_session()
+            .check(program)?;
+        self.evaluator.execute(program)?;
+
+        Ok(())
+    }
+}
+
+/// Whether `input` can be run, or is still waiting for a closing quote,
+/// comment or bracket.
+fn is_complete(input: &str) -> bool {
+    let tokens = match Lexer::new(input).lex() {
+        Ok(tokens) => tokens,
+        Err(LexError::UnterminatedString { .. } | LexError::UnterminatedComment { .. }) => {
+            return false
+        }
+        // Running it reports the error.
+        Err(_) => return true,
+    };
+
+    let depth: i64 = tokens
+        .iter()
+        .map(|token| match token.token_type {
+            TokenType::LBrace | TokenType::LParen | TokenType::LBracket => 1,
+            TokenType::RBrace | TokenType::RParen | TokenType::RBracket => -1,
+            _ => 0,
+        })
+        .sum();
+
+    depth <= 0
+}
+
+/// Whether `tokens` start like a statement rather than a bare expression:
+/// with a statement keyword, a named `func`, or an assignment.
+fn looks_like_statement(tokens: &[Token]) -> bool {
+    let mut types = tokens
+        .iter()
+        .map(|token| token.token_type)
+        .filter(|token_type| *token_type != TokenType::DocComment);
+
+    let starts_statement = match types.next() {
+        Some(
+            TokenType::Let
+            | TokenType::If
+            | TokenType::While
+            | TokenType::For
+            | TokenType::Break
+            | TokenType::Continue
+            | TokenType::Struct
+            | TokenType::Return
+            | TokenType::Print,
+        ) => true,
+        Some(TokenType::Func) => types.next() == Some(TokenType::Identif),
+        _ => false,
+    };
+
+    starts_statement
+        || tokens
+            .iter()
+            .any(|token| token.token_type == TokenType::Assign)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_errors(input: &str) -> Vec<String> {
+        let tokens = Lexer::new(input).lex().expect("input should lex");
+        match Repl::new().parse(tokens) {
+            Err(IlumniaError::Parse(errors)) => errors.iter().map(ToString::to_string).collect(),
+            other => panic!("{}: {:?}", input, other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reports_expression_errors_for_bare_expressions() {
+        assert_eq!(parse_errors("1 +"), ["1:4: Unexpected token: Eof"]);
+    }
+
+    #[test]
+    fn reports_statement_errors_for_statements() {
+        assert_eq!(
+            parse_errors("let a = ;"),
+            ["1:9: Unexpected token: Semicolon"]
+        );
+        assert_eq!(parse_errors("a = 1 +"), ["1:8: Unexpected token: Eof"]);
+    }
+}